@@ -0,0 +1,123 @@
+use crate::scanner::Span;
+use crate::symbol::Symbol;
+use std::fmt;
+use std::rc::Rc;
+
+// Operands index into the chunk's constant pool unless noted otherwise
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Op {
+    Constant(u32),
+    Nil,
+    True,
+    False,
+    Pop,
+    // Stack slot relative to the current call frame
+    GetLocal(u32),
+    SetLocal(u32),
+    GetGlobal(u32),
+    DefineGlobal(u32),
+    SetGlobal(u32),
+    // Index into the current closure's upvalues
+    GetUpvalue(u32),
+    SetUpvalue(u32),
+    GetProperty(u32),
+    SetProperty(u32),
+    GetSuper(u32),
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    // Joins the string forms of the top n values
+    Interpolate(u32),
+    Print,
+    // Jump targets are absolute offsets into the chunk
+    Jump(u32),
+    JumpIfFalse(u32),
+    // Argument count
+    Call(u8),
+    // A method call on an instance without creating a bound method first
+    Invoke(u32, u8),
+    SuperInvoke(u32, u8),
+    Closure(u32),
+    CloseUpvalue,
+    Return,
+    Class(u32),
+    Inherit,
+    Method(u32),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Constant {
+    Num(f64),
+    Str(Symbol),
+    Function(Rc<Function>),
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Num(n) => write!(f, "{n}"),
+            Constant::Str(s) => write!(f, "{s}"),
+            Constant::Function(function) => write!(f, "{function}"),
+        }
+    }
+}
+
+// Compiled code for one function. `lines` and `spans` hold the source
+// location of each op.
+#[derive(Debug, PartialEq, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub constants: Vec<Constant>,
+    pub lines: Vec<i32>,
+    pub spans: Vec<Span>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, op: Op, line: i32, span: Span) -> usize {
+        self.code.push(op);
+        self.lines.push(line);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+
+    pub fn add_constant(&mut self, constant: Constant) -> u32 {
+        self.constants.push(constant);
+        (self.constants.len() - 1) as u32
+    }
+}
+
+// Where a closure finds a captured variable when it is created: a local slot
+// of the enclosing function, or one of the enclosing closure's own upvalues
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct UpvalueRef {
+    pub is_local: bool,
+    pub index: u32,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct Function {
+    // Empty for the top-level script
+    pub name: String,
+    pub arity: usize,
+    pub upvalues: Vec<UpvalueRef>,
+    pub chunk: Chunk,
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "<script>")
+        } else {
+            write!(f, "<fn {}>", self.name)
+        }
+    }
+}
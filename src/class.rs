@@ -0,0 +1,104 @@
+use crate::error_code::ErrorCode;
+use crate::function::{Callable, LoxFunction};
+use crate::interpreter::{Interpreter, RuntimeError, Value};
+use crate::scanner::Token;
+use crate::symbol::{self, Symbol};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+pub struct LoxClass<'src> {
+    pub name: &'src str,
+    superclass: Option<Rc<LoxClass<'src>>>,
+    methods: HashMap<Symbol, Rc<LoxFunction<'src>>>,
+}
+
+impl<'src> LoxClass<'src> {
+    pub fn new(
+        name: &'src str,
+        superclass: Option<Rc<LoxClass<'src>>>,
+        methods: HashMap<Symbol, Rc<LoxFunction<'src>>>,
+    ) -> Self {
+        LoxClass {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    pub fn find_method(&self, name: Symbol) -> Option<Rc<LoxFunction<'src>>> {
+        match self.methods.get(&name) {
+            Some(method) => Some(method.clone()),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+}
+
+impl<'src> Callable<'src> for LoxClass<'src> {
+    fn arity(&self) -> usize {
+        self.find_method(*symbol::INIT)
+            .map_or(0, |init| init.arity())
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter<'src>,
+        arguments: Vec<Value<'src>>,
+    ) -> Result<Value<'src>, RuntimeError> {
+        let instance = Rc::new(RefCell::new(LoxInstance::new(self.clone())));
+        if let Some(init) = self.find_method(*symbol::INIT) {
+            Rc::new(init.bind(instance.clone())).call(interpreter, arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl fmt::Display for LoxClass<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub struct LoxInstance<'src> {
+    class: Rc<LoxClass<'src>>,
+    fields: HashMap<Symbol, Value<'src>>,
+}
+
+impl<'src> LoxInstance<'src> {
+    fn new(class: Rc<LoxClass<'src>>) -> Self {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    // Fields shadow methods; methods are bound to `instance` on access
+    pub fn get(
+        instance: &Rc<RefCell<LoxInstance<'src>>>,
+        name: &Token<'src>,
+    ) -> Result<Value<'src>, RuntimeError> {
+        let this = instance.borrow();
+        if let Some(value) = this.fields.get(&name.name()) {
+            return Ok(value.clone());
+        }
+        match this.class.find_method(name.name()) {
+            Some(method) => Ok(Value::Callable(Rc::new(method.bind(instance.clone())))),
+            None => Err(RuntimeError::new(
+                name,
+                ErrorCode::UndefinedProperty,
+                &format!("Undefined property '{}'.", name.lexeme),
+            )),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token<'src>, value: Value<'src>) {
+        self.fields.insert(name.name(), value);
+    }
+}
+
+impl fmt::Display for LoxInstance<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
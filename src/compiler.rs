@@ -0,0 +1,612 @@
+use crate::chunk::{Chunk, Constant, Function, Op, UpvalueRef};
+use crate::diagnostics::SourceFile;
+use crate::parser::{Expression, FunctionDecl, Literal, Stmt};
+use crate::scanner::{Span, TokenType};
+use crate::symbol::{self, Symbol};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Programs are only compiled once they have passed the resolver, so
+// compilation can't fail
+
+pub fn compile<'a>(file: &'a SourceFile<'a>, statements: &[Stmt<'a>]) -> Rc<Function> {
+    let mut compiler = Compiler::new(file);
+    for stmt in statements {
+        compiler.statement(stmt);
+    }
+    compiler.emit_here(Op::Nil);
+    compiler.emit_here(Op::Return);
+    compiler.finish()
+}
+
+// A script that returns the value of `expr`
+pub fn compile_expression<'a>(file: &'a SourceFile<'a>, expr: &Expression<'a>) -> Rc<Function> {
+    let mut compiler = Compiler::new(file);
+    compiler.expression(expr);
+    compiler.emit_here(Op::Return);
+    compiler.finish()
+}
+
+#[derive(PartialEq, Copy, Clone)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: Symbol,
+    depth: usize,
+    // Whether a closure captures the variable, so it must be moved off the
+    // stack when it goes out of scope
+    captured: bool,
+}
+
+// A function being compiled
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    // Slot 0 holds the function itself, or `this` in methods
+    locals: Vec<Local>,
+    scope_depth: usize,
+    // Constant pool indexes of names, so each is only stored once
+    names: HashMap<Symbol, u32>,
+}
+
+impl FunctionState {
+    fn new(name: &str, kind: FunctionKind) -> Self {
+        let slot_zero = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => *symbol::THIS,
+            FunctionKind::Script | FunctionKind::Function => Symbol::intern(""),
+        };
+        FunctionState {
+            function: Function {
+                name: name.to_owned(),
+                ..Function::default()
+            },
+            kind,
+            locals: vec![Local {
+                name: slot_zero,
+                depth: 0,
+                captured: false,
+            }],
+            scope_depth: if kind == FunctionKind::Script { 0 } else { 1 },
+            names: HashMap::new(),
+        }
+    }
+
+    fn resolve_local(&self, name: Symbol) -> Option<u32> {
+        self.locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u32)
+    }
+}
+
+struct Compiler<'a> {
+    file: &'a SourceFile<'a>,
+    // The innermost function is last
+    functions: Vec<FunctionState>,
+    // Where the last op came from, for ops that don't correspond to any
+    // particular piece of syntax
+    last_span: Span,
+    // The line `last_span` starts on, so runs of ops from the same syntax
+    // only look it up once
+    last_line: i32,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(file: &'a SourceFile<'a>) -> Self {
+        Compiler {
+            file,
+            functions: vec![FunctionState::new("", FunctionKind::Script)],
+            last_span: Span::default(),
+            last_line: 1,
+        }
+    }
+
+    fn finish(mut self) -> Rc<Function> {
+        Rc::new(self.functions.pop().unwrap().function)
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current().function.chunk
+    }
+
+    fn emit(&mut self, op: Op, span: Span) -> usize {
+        if span != self.last_span {
+            self.last_line = self.file.line(span.start) as i32;
+            self.last_span = span;
+        }
+        let line = self.last_line;
+        self.chunk().write(op, line, span)
+    }
+
+    fn emit_here(&mut self, op: Op) -> usize {
+        self.emit(op, self.last_span)
+    }
+
+    fn constant(&mut self, constant: Constant) -> u32 {
+        self.chunk().add_constant(constant)
+    }
+
+    fn name(&mut self, name: Symbol) -> u32 {
+        if let Some(&index) = self.current().names.get(&name) {
+            return index;
+        }
+        let index = self.constant(Constant::Str(name));
+        self.current().names.insert(name, index);
+        index
+    }
+
+    // Emits a jump to be pointed at its target later with `patch`
+    fn jump(&mut self, op: fn(u32) -> Op) -> usize {
+        self.emit_here(op(u32::MAX))
+    }
+
+    fn patch(&mut self, jump: usize) {
+        let target = self.chunk().code.len() as u32;
+        let op = &mut self.chunk().code[jump];
+        *op = match op {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            _ => unreachable!("patching non-jump {op:?}"),
+        };
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let state = self.current();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+        while let Some(local) = self.current().locals.pop_if(|local| local.depth > depth) {
+            self.emit_here(if local.captured {
+                Op::CloseUpvalue
+            } else {
+                Op::Pop
+            });
+        }
+    }
+
+    fn add_local(&mut self, name: Symbol) {
+        let state = self.current();
+        state.locals.push(Local {
+            name,
+            depth: state.scope_depth,
+            captured: false,
+        });
+    }
+
+    // Makes the value on top of the stack the variable `name`: in globals
+    // at the top level, otherwise by leaving it in place as a local
+    fn define(&mut self, name: Symbol, span: Span) {
+        if self.current().scope_depth > 0 {
+            self.add_local(name);
+        } else {
+            let name = self.name(name);
+            self.emit(Op::DefineGlobal(name), span);
+        }
+    }
+
+    // Finds `name` among the variables captured by the function at `level`
+    fn resolve_upvalue(&mut self, level: usize, name: Symbol) -> Option<u32> {
+        let enclosing = level.checked_sub(1)?;
+        if let Some(slot) = self.functions[enclosing].resolve_local(name) {
+            self.functions[enclosing].locals[slot as usize].captured = true;
+            return Some(self.add_upvalue(level, true, slot));
+        }
+        let index = self.resolve_upvalue(enclosing, name)?;
+        Some(self.add_upvalue(level, false, index))
+    }
+
+    fn add_upvalue(&mut self, level: usize, is_local: bool, index: u32) -> u32 {
+        let upvalue = UpvalueRef { is_local, index };
+        let upvalues = &mut self.functions[level].function.upvalues;
+        match upvalues.iter().position(|&u| u == upvalue) {
+            Some(i) => i as u32,
+            None => {
+                upvalues.push(upvalue);
+                (upvalues.len() - 1) as u32
+            }
+        }
+    }
+
+    // The ops that read and write the variable `name`
+    fn variable(&mut self, name: Symbol) -> (Op, Op) {
+        if let Some(slot) = self.current().resolve_local(name) {
+            return (Op::GetLocal(slot), Op::SetLocal(slot));
+        }
+        if let Some(index) = self.resolve_upvalue(self.functions.len() - 1, name) {
+            return (Op::GetUpvalue(index), Op::SetUpvalue(index));
+        }
+        let name = self.name(name);
+        (Op::GetGlobal(name), Op::SetGlobal(name))
+    }
+
+    fn get_variable(&mut self, name: Symbol, span: Span) {
+        let (get, _) = self.variable(name);
+        self.emit(get, span);
+    }
+
+    fn statement(&mut self, stmt: &Stmt<'a>) {
+        match stmt {
+            Stmt::Expression(expr) => {
+                self.expression(expr);
+                self.emit_here(Op::Pop);
+            }
+            Stmt::Print(expr) => {
+                self.expression(expr);
+                self.emit_here(Op::Print);
+            }
+            Stmt::Var { name, initializer } => {
+                match initializer {
+                    Some(expr) => self.expression(expr),
+                    None => {
+                        self.emit(Op::Nil, name.span);
+                    }
+                }
+                self.define(name.name(), name.span);
+            }
+            Stmt::Block(statements) => {
+                self.begin_scope();
+                for stmt in statements {
+                    self.statement(stmt);
+                }
+                self.end_scope();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                let then_jump = self.jump(Op::JumpIfFalse);
+                self.emit_here(Op::Pop);
+                self.statement(then_branch);
+                let else_jump = self.jump(Op::Jump);
+                self.patch(then_jump);
+                self.emit_here(Op::Pop);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+                self.patch(else_jump);
+            }
+            Stmt::While { condition, body } => {
+                let loop_start = self.chunk().code.len() as u32;
+                self.expression(condition);
+                let exit_jump = self.jump(Op::JumpIfFalse);
+                self.emit_here(Op::Pop);
+                self.statement(body);
+                self.emit_here(Op::Jump(loop_start));
+                self.patch(exit_jump);
+                self.emit_here(Op::Pop);
+            }
+            Stmt::Function(declaration) => {
+                // A local function is in scope in its own body, so that it can
+                // call itself
+                let name = &declaration.name;
+                let local = self.current().scope_depth > 0;
+                if local {
+                    self.add_local(name.name());
+                }
+                self.function(declaration, FunctionKind::Function);
+                if !local {
+                    self.define(name.name(), name.span);
+                }
+            }
+            Stmt::Return { keyword, value } => {
+                match value {
+                    Some(expr) => self.expression(expr),
+                    None => self.implicit_return_value(keyword.span),
+                }
+                self.emit(Op::Return, keyword.span);
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let class = self.name(name.name());
+                let local = self.current().scope_depth > 0;
+                if local {
+                    self.add_local(name.name());
+                }
+                self.emit(Op::Class(class), name.span);
+                if !local {
+                    self.define(name.name(), name.span);
+                }
+
+                // Methods of a subclass close over an extra scope binding
+                // `super`
+                if let Some(superclass) = superclass {
+                    self.begin_scope();
+                    self.expression(superclass);
+                    self.add_local(*symbol::SUPER);
+                    self.get_variable(name.name(), name.span);
+                    self.emit(Op::Inherit, superclass.span());
+                }
+
+                self.get_variable(name.name(), name.span);
+                for method in methods {
+                    let kind = if method.name.name() == *symbol::INIT {
+                        FunctionKind::Initializer
+                    } else {
+                        FunctionKind::Method
+                    };
+                    self.function(method, kind);
+                    let name = self.name(method.name.name());
+                    self.emit(Op::Method(name), method.name.span);
+                }
+                self.emit_here(Op::Pop);
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+            }
+        }
+    }
+
+    // Compiles `declaration` into its own chunk and emits the op creating a
+    // closure over it
+    fn function(&mut self, declaration: &FunctionDecl<'a>, kind: FunctionKind) {
+        let mut state = FunctionState::new(declaration.name.lexeme, kind);
+        state.function.arity = declaration.params.len();
+        self.functions.push(state);
+        for param in &declaration.params {
+            self.add_local(param.name());
+        }
+        for stmt in &declaration.body {
+            self.statement(stmt);
+        }
+        self.implicit_return_value(self.last_span);
+        self.emit_here(Op::Return);
+
+        let function = self.functions.pop().unwrap().function;
+        let function = self.constant(Constant::Function(Rc::new(function)));
+        self.emit(Op::Closure(function), declaration.name.span);
+    }
+
+    // Initializers always return the instance; other functions return nil
+    fn implicit_return_value(&mut self, span: Span) {
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit(Op::GetLocal(0), span);
+        } else {
+            self.emit(Op::Nil, span);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression<'a>) {
+        match expr {
+            Expression::Literal(literal, span) => {
+                let op = match literal {
+                    Literal::Num(n) => Op::Constant(self.constant(Constant::Num(*n))),
+                    Literal::Str(s) => Op::Constant(self.constant(Constant::Str(*s))),
+                    Literal::Bool(true) => Op::True,
+                    Literal::Bool(false) => Op::False,
+                    Literal::Nil => Op::Nil,
+                };
+                self.emit(op, *span);
+            }
+            Expression::Unary { op, e } => {
+                self.expression(e);
+                let op = match op.kind {
+                    TokenType::Minus => Op::Negate,
+                    TokenType::Not => Op::Not,
+                    _ => unreachable!("invalid unary operator {:?}", op.kind),
+                };
+                self.emit(op, expr.span());
+            }
+            Expression::Binary { e1, op, e2 } => {
+                self.expression(e1);
+                self.expression(e2);
+                let op = match op.kind {
+                    TokenType::EqualEqual => Op::Equal,
+                    TokenType::NotEqual => Op::NotEqual,
+                    TokenType::Greater => Op::Greater,
+                    TokenType::GreaterEqual => Op::GreaterEqual,
+                    TokenType::Less => Op::Less,
+                    TokenType::LessEqual => Op::LessEqual,
+                    TokenType::Plus => Op::Add,
+                    TokenType::Minus => Op::Subtract,
+                    TokenType::Times => Op::Multiply,
+                    TokenType::Divide => Op::Divide,
+                    _ => unreachable!("invalid binary operator {:?}", op.kind),
+                };
+                self.emit(op, expr.span());
+            }
+            Expression::Logical { e1, op, e2 } => {
+                self.expression(e1);
+                if op.kind == TokenType::Or {
+                    let else_jump = self.jump(Op::JumpIfFalse);
+                    let end_jump = self.jump(Op::Jump);
+                    self.patch(else_jump);
+                    self.emit_here(Op::Pop);
+                    self.expression(e2);
+                    self.patch(end_jump);
+                } else {
+                    let end_jump = self.jump(Op::JumpIfFalse);
+                    self.emit_here(Op::Pop);
+                    self.expression(e2);
+                    self.patch(end_jump);
+                }
+            }
+            Expression::Variable { name, .. } => self.get_variable(name.name(), name.span),
+            Expression::Assign { name, value, .. } => {
+                self.expression(value);
+                let (_, set) = self.variable(name.name());
+                self.emit(set, name.span);
+            }
+            Expression::Call {
+                callee, arguments, ..
+            } => {
+                let argc = arguments.len() as u8;
+                match &**callee {
+                    Expression::Get { object, name } => {
+                        self.expression(object);
+                        self.arguments(arguments);
+                        let name = self.name(name.name());
+                        self.emit(Op::Invoke(name, argc), expr.span());
+                    }
+                    Expression::Super {
+                        keyword, method, ..
+                    } => {
+                        self.get_variable(*symbol::THIS, keyword.span);
+                        self.arguments(arguments);
+                        self.get_variable(*symbol::SUPER, keyword.span);
+                        let name = self.name(method.name());
+                        self.emit(Op::SuperInvoke(name, argc), expr.span());
+                    }
+                    _ => {
+                        self.expression(callee);
+                        self.arguments(arguments);
+                        self.emit(Op::Call(argc), expr.span());
+                    }
+                }
+            }
+            Expression::Get { object, name } => {
+                self.expression(object);
+                let name_index = self.name(name.name());
+                self.emit(Op::GetProperty(name_index), name.span);
+            }
+            Expression::Set {
+                object,
+                name,
+                value,
+            } => {
+                self.expression(object);
+                self.expression(value);
+                let name_index = self.name(name.name());
+                self.emit(Op::SetProperty(name_index), name.span);
+            }
+            Expression::This { keyword, .. } => self.get_variable(*symbol::THIS, keyword.span),
+            Expression::Super {
+                keyword, method, ..
+            } => {
+                self.get_variable(*symbol::THIS, keyword.span);
+                self.get_variable(*symbol::SUPER, keyword.span);
+                let name = self.name(method.name());
+                self.emit(Op::GetSuper(name), method.span);
+            }
+            Expression::Interpolation(parts) => {
+                for part in parts {
+                    self.expression(part);
+                }
+                self.emit(Op::Interpolate(parts.len() as u32), expr.span());
+            }
+        }
+    }
+
+    fn arguments(&mut self, arguments: &[Expression<'a>]) {
+        for argument in arguments {
+            self.expression(argument);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn compile_source(source: &str) -> Rc<Function> {
+        let file = SourceFile::new("test.lox", source);
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        compile(&file, &program)
+    }
+
+    #[test]
+    fn globals_and_locals() {
+        let script = compile_source("var a = 1;\n{ var b = a; b = 2; }");
+        assert_eq!(
+            script.chunk.code,
+            vec![
+                Op::Constant(0),
+                Op::DefineGlobal(1),
+                Op::GetGlobal(1),
+                Op::Constant(2),
+                Op::SetLocal(1),
+                Op::Pop,
+                Op::Pop,
+                Op::Nil,
+                Op::Return,
+            ]
+        );
+        assert_eq!(
+            script.chunk.constants,
+            vec![
+                Constant::Num(1.0),
+                Constant::Str(Symbol::intern("a")),
+                Constant::Num(2.0),
+            ]
+        );
+        assert_eq!(script.chunk.lines, vec![1, 1, 2, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn jumps() {
+        let script = compile_source("while (true) if (false) print 1; else print 2;");
+        assert_eq!(
+            script.chunk.code,
+            vec![
+                Op::True,
+                Op::JumpIfFalse(13),
+                Op::Pop,
+                Op::False,
+                Op::JumpIfFalse(9),
+                Op::Pop,
+                Op::Constant(0),
+                Op::Print,
+                Op::Jump(12),
+                Op::Pop,
+                Op::Constant(1),
+                Op::Print,
+                Op::Jump(0),
+                Op::Pop,
+                Op::Nil,
+                Op::Return,
+            ]
+        );
+    }
+
+    #[test]
+    fn closures() {
+        let script =
+            compile_source("fun outer() { var x = 1; fun middle() { fun inner() { return x; } } }");
+        let Constant::Function(outer) = &script.chunk.constants[0] else {
+            panic!("expected function, found {:?}", script.chunk.constants[0]);
+        };
+        assert_eq!(outer.to_string(), "<fn outer>");
+        assert_eq!(outer.chunk.code[1], Op::Closure(1));
+        let Constant::Function(middle) = &outer.chunk.constants[1] else {
+            panic!("expected function, found {:?}", outer.chunk.constants[1]);
+        };
+        let Constant::Function(inner) = &middle.chunk.constants[0] else {
+            panic!("expected function, found {:?}", middle.chunk.constants[0]);
+        };
+        assert_eq!(
+            middle.upvalues,
+            vec![UpvalueRef {
+                is_local: true,
+                index: 1
+            }]
+        );
+        assert_eq!(
+            inner.upvalues,
+            vec![UpvalueRef {
+                is_local: false,
+                index: 0
+            }]
+        );
+        assert_eq!(inner.chunk.code[0], Op::GetUpvalue(0));
+    }
+}
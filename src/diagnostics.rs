@@ -0,0 +1,410 @@
+use crate::error_code::ErrorCode;
+use crate::scanner::Span;
+use std::fmt::Write;
+use std::io::{self, IsTerminal};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+impl ErrorFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "human" => Some(ErrorFormat::Human),
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+pub trait ToDiagnostic {
+    fn to_diagnostic(&self) -> Diagnostic;
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: &str) -> Self {
+        Label {
+            span,
+            message: message.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<ErrorCode>,
+    pub message: String,
+    // Missing when the error is about the file as a whole
+    pub primary: Option<Label>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    // The primary label starts out empty; the headline message is shown above
+    // the snippet
+    pub fn error(message: &str, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code: None,
+            message: message.to_owned(),
+            primary: Some(Label::new(span, "")),
+            secondary: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    // An error with no location in the file, such as failing to read it
+    pub fn file_error(message: &str) -> Self {
+        Diagnostic {
+            primary: None,
+            ..Diagnostic::error(message, Span::new(0, 0))
+        }
+    }
+
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, message: &str) -> Self {
+        if let Some(primary) = &mut self.primary {
+            primary.message = message.to_owned();
+        }
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: &str) -> Self {
+        self.secondary.push(Label::new(span, message));
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_owned());
+        self
+    }
+
+    pub fn render(&self, file: &SourceFile, color: bool) -> String {
+        let paint = |style: &'static str| if color { style } else { "" };
+        let reset = paint(RESET);
+
+        let mut labels: Vec<(&Label, bool)> =
+            self.primary.iter().map(|label| (label, true)).collect();
+        labels.extend(self.secondary.iter().map(|label| (label, false)));
+        labels.sort_by_key(|(label, _)| label.span.start);
+        let max_line = labels
+            .iter()
+            .map(|(label, _)| file.line_col(label.span.start).0)
+            .max()
+            .unwrap_or(1);
+        let width = max_line.to_string().len();
+        let gutter = format!("{}{:width$} |{reset}", paint(BLUE), "");
+
+        let code = match self.code {
+            Some(code) => format!("[{code}]"),
+            None => String::new(),
+        };
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}{}{code}{reset}{}: {}{reset}",
+            paint(self.severity.color()),
+            self.severity.name(),
+            paint(BOLD),
+            self.message
+        );
+        let location = match &self.primary {
+            Some(primary) => {
+                let (line, column) = file.line_col(primary.span.start);
+                format!(":{line}:{column}")
+            }
+            None => String::new(),
+        };
+        let _ = writeln!(
+            out,
+            "{}{:width$}--> {reset}{}{location}",
+            paint(BLUE),
+            "",
+            file.name
+        );
+        if !labels.is_empty() {
+            let _ = writeln!(out, "{gutter}");
+        }
+
+        let mut last_line = None;
+        for (label, is_primary) in labels {
+            let (line, _) = file.line_col(label.span.start);
+            let text = file.line_text(line);
+            if last_line != Some(line) {
+                let _ = writeln!(out, "{}{line:>width$} |{reset} {text}", paint(BLUE));
+                last_line = Some(line);
+            }
+            // Spans reaching past the end of the line are cut off there
+            let line_end = file.line_start(line) + text.len();
+            let start = label.span.start.min(line_end);
+            let end = label.span.end.clamp(start, line_end);
+            let pad = file.source[file.line_start(line)..start].chars().count();
+            let len = file.source[start..end].chars().count().max(1);
+            let (mark, style) = if is_primary {
+                ('^', self.severity.color())
+            } else {
+                ('-', BLUE)
+            };
+            let underline = mark.to_string().repeat(len);
+            let mut row = format!("{gutter} {:pad$}{}{underline}", "", paint(style));
+            if !label.message.is_empty() {
+                let _ = write!(row, " {}", label.message);
+            }
+            let _ = writeln!(out, "{row}{reset}");
+        }
+
+        if !self.notes.is_empty() {
+            let _ = writeln!(out, "{gutter}");
+        }
+        for note in &self.notes {
+            let _ = writeln!(
+                out,
+                "{}{:width$} = {reset}{}note{reset}: {note}",
+                paint(BLUE),
+                "",
+                paint(BOLD)
+            );
+        }
+        out
+    }
+
+    // A single-line JSON object; labels and notes are left out. Errors about
+    // the whole file have a null span, line and column.
+    pub fn to_json(&self, file: &SourceFile) -> String {
+        let location = match &self.primary {
+            Some(primary) => {
+                let (line, column) = file.line_col(primary.span.start);
+                format!(
+                    "{{\"start\":{},\"end\":{}}},\"line\":{line},\"column\":{column}",
+                    primary.span.start, primary.span.end
+                )
+            }
+            None => "null,\"line\":null,\"column\":null".to_owned(),
+        };
+        let code = match self.code {
+            Some(code) => json_string(code.as_str()),
+            None => "null".to_owned(),
+        };
+        format!(
+            "{{\"file\":{},\"span\":{location},\"severity\":{},\"code\":{code},\"message\":{}}}",
+            json_string(file.name),
+            json_string(self.severity.name()),
+            json_string(&self.message)
+        )
+    }
+
+    pub fn emit(&self, file: &SourceFile, format: ErrorFormat) {
+        match format {
+            ErrorFormat::Human => eprint!("{}", self.render(file, io::stderr().is_terminal())),
+            ErrorFormat::Json => eprintln!("{}", self.to_json(file)),
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub struct SourceFile<'a> {
+    pub name: &'a str,
+    pub source: &'a str,
+    // Byte offset at which each line begins
+    line_starts: Vec<usize>,
+}
+
+impl<'a> SourceFile<'a> {
+    pub fn new(name: &'a str, source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceFile {
+            name,
+            source,
+            line_starts,
+        }
+    }
+
+    // 1-based line of a byte offset
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset)
+    }
+
+    // 1-based line and column (in characters) of a byte offset
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line(offset);
+        let start = self.line_starts[line - 1];
+        let column = self.source[start..offset.min(self.source.len())]
+            .chars()
+            .count();
+        (line, column + 1)
+    }
+
+    fn line_start(&self, line: usize) -> usize {
+        self.line_starts[line - 1]
+    }
+
+    fn line_text(&self, line: usize) -> &'a str {
+        let start = self.line_start(line);
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |&next| next - 1);
+        self.source[start..end].trim_end_matches('\r')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_plain() {
+        let source = "var a = 1;\nvar a = 2;\nprint a +;\n";
+        let file = SourceFile::new("test.lox", source);
+        let diagnostic = Diagnostic::error("expected expression", Span::new(31, 32))
+            .with_label("expected an operand here")
+            .with_secondary(Span::new(30, 31), "operator needs a right-hand side")
+            .with_note("binary operators take two operands");
+        assert_eq!(
+            diagnostic.render(&file, false),
+            "\
+error: expected expression
+ --> test.lox:3:10
+  |
+3 | print a +;
+  |         - operator needs a right-hand side
+  |          ^ expected an operand here
+  |
+  = note: binary operators take two operands
+"
+        );
+    }
+
+    #[test]
+    fn render_across_lines() {
+        let source = "{\n  var x = 1;\n  var x = 2;\n}";
+        let file = SourceFile::new("<repl>", source);
+        let diagnostic = Diagnostic::error("duplicate variable", Span::new(21, 22))
+            .with_secondary(Span::new(8, 9), "first declared here");
+        assert_eq!(
+            diagnostic.render(&file, false),
+            "\
+error: duplicate variable
+ --> <repl>:3:7
+  |
+2 |   var x = 1;
+  |       - first declared here
+3 |   var x = 2;
+  |       ^
+"
+        );
+        assert!(diagnostic.render(&file, true).contains("\x1b[1;31m^"));
+    }
+
+    #[test]
+    fn render_past_line_end() {
+        // The span covers the `\n` of a CRLF line ending
+        let file = SourceFile::new("test.lox", "print 1\r\nprint 2;");
+        let diagnostic = Diagnostic::error("expected ';' after value", Span::new(8, 9));
+        assert_eq!(
+            diagnostic.render(&file, false),
+            "\
+error: expected ';' after value
+ --> test.lox:1:9
+  |
+1 | print 1
+  |        ^
+"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let source = "print 1;\nprint x;";
+        let file = SourceFile::new("dir/\"q\".lox", source);
+        let diagnostic = Diagnostic::error("undefined variable 'x'", Span::new(15, 16))
+            .with_label("not found in this scope");
+        assert_eq!(
+            diagnostic.to_json(&file),
+            r#"{"file":"dir/\"q\".lox","span":{"start":15,"end":16},"line":2,"column":7,"severity":"error","code":null,"message":"undefined variable 'x'"}"#
+        );
+        assert_eq!(json_string("a\tb\n\\\u{1}é"), r#""a\tb\n\\\u0001é""#);
+    }
+
+    #[test]
+    fn file_error() {
+        let file = SourceFile::new("missing.lox", "");
+        let diagnostic = Diagnostic::file_error("couldn't read file: not found");
+        assert_eq!(
+            diagnostic.render(&file, false),
+            "error: couldn't read file: not found\n --> missing.lox\n"
+        );
+        assert_eq!(
+            diagnostic.to_json(&file),
+            r#"{"file":"missing.lox","span":null,"line":null,"column":null,"severity":"error","code":null,"message":"couldn't read file: not found"}"#
+        );
+    }
+
+    #[test]
+    fn line_col() {
+        let file = SourceFile::new("f", "ab\né\nc");
+        assert_eq!(file.line_col(0), (1, 1));
+        assert_eq!(file.line_col(3), (2, 1));
+        assert_eq!(file.line_col(5), (2, 2));
+        assert_eq!(file.line_col(7), (3, 2));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::chunk::{Chunk, Constant, Function, Op};
+use std::fmt::Write;
+
+// Lists the ops of `function` followed by those of every function nested in
+// it, e.g.
+//
+// == <fn add> ==
+// 0000    1 GetLocal            1
+// 0001    | GetLocal            2
+// 0002    | Add
+// 0003    | Return
+pub fn disassemble(function: &Function) -> String {
+    let mut out = String::new();
+    disassemble_into(&mut out, function);
+    out
+}
+
+fn disassemble_into(out: &mut String, function: &Function) {
+    writeln!(out, "== {function} ==").unwrap();
+    let chunk = &function.chunk;
+    for offset in 0..chunk.code.len() {
+        out.push_str(&instruction(chunk, offset));
+    }
+    for constant in &chunk.constants {
+        if let Constant::Function(nested) = constant {
+            out.push('\n');
+            disassemble_into(out, nested);
+        }
+    }
+}
+
+// One op with its offset, source line and operands. Closures are followed by
+// a line per captured variable.
+pub fn instruction(chunk: &Chunk, offset: usize) -> String {
+    let op = chunk.code[offset];
+    let mut out = format!("{offset:04} ");
+    if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
+        out.push_str("   | ");
+    } else {
+        write!(out, "{:4} ", chunk.lines[offset]).unwrap();
+    }
+    let name = format!("{op:?}");
+    let name = name.split('(').next().unwrap();
+    let constant = |index: u32| format!("{index:4} '{}'", chunk.constants[index as usize]);
+    let operands = match op {
+        Op::Constant(index)
+        | Op::GetGlobal(index)
+        | Op::DefineGlobal(index)
+        | Op::SetGlobal(index)
+        | Op::GetProperty(index)
+        | Op::SetProperty(index)
+        | Op::GetSuper(index)
+        | Op::Closure(index)
+        | Op::Class(index)
+        | Op::Method(index) => constant(index),
+        Op::GetLocal(n)
+        | Op::SetLocal(n)
+        | Op::GetUpvalue(n)
+        | Op::SetUpvalue(n)
+        | Op::Interpolate(n) => format!("{n:4}"),
+        Op::Call(argc) => format!("{argc:4}"),
+        Op::Invoke(index, argc) | Op::SuperInvoke(index, argc) => {
+            format!("{} ({argc} args)", constant(index))
+        }
+        Op::Jump(target) | Op::JumpIfFalse(target) => format!("  -> {target:04}"),
+        _ => String::new(),
+    };
+    writeln!(out, "{name:<16} {operands}").unwrap();
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+
+    if let Op::Closure(index) = op {
+        let Constant::Function(function) = &chunk.constants[index as usize] else {
+            unreachable!("closures are always made from functions");
+        };
+        for upvalue in &function.upvalues {
+            let kind = if upvalue.is_local { "local" } else { "upvalue" };
+            writeln!(out, "{offset:04}    |   {kind} {}", upvalue.index).unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler;
+    use crate::diagnostics::SourceFile;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn disassembly() {
+        let source =
+            "var x = \"hi\";\nfun f(a) {\n  fun g() { return a; }\n  while (a) a = g();\n}";
+        let file = SourceFile::new("test.lox", source);
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let script = compiler::compile(&file, &program);
+        assert_eq!(
+            disassemble(&script),
+            "\
+== <script> ==
+0000    1 Constant            0 'hi'
+0001    | DefineGlobal        1 'x'
+0002    2 Closure             2 '<fn f>'
+0003    | DefineGlobal        3 'f'
+0004    | Nil
+0005    | Return
+
+== <fn f> ==
+0000    3 Closure             0 '<fn g>'
+0000    |   local 1
+0001    4 GetLocal            1
+0002    | JumpIfFalse        -> 0009
+0003    | Pop
+0004    | GetLocal            2
+0005    | Call                0
+0006    | SetLocal            1
+0007    | Pop
+0008    | Jump               -> 0001
+0009    | Pop
+0010    | Nil
+0011    | Return
+
+== <fn g> ==
+0000    3 GetUpvalue          0
+0001    | Return
+0002    | Nil
+0003    | Return
+"
+        );
+    }
+}
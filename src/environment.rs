@@ -0,0 +1,167 @@
+use crate::error_code::ErrorCode;
+use crate::interpreter::{RuntimeError, Value};
+use crate::scanner::Token;
+use crate::symbol::Symbol;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Debug, Default)]
+pub struct Environment<'src> {
+    values: HashMap<Symbol, Value<'src>>,
+    enclosing: Option<Rc<RefCell<Environment<'src>>>>,
+}
+
+impl<'src> Environment<'src> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment<'src>>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    pub fn define(&mut self, name: Symbol, value: Value<'src>) {
+        self.values.insert(name, value);
+    }
+
+    pub fn get_local(&self, name: Symbol) -> Option<Value<'src>> {
+        self.values.get(&name).cloned()
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value<'src>, RuntimeError> {
+        if let Some(value) = self.values.get(&name.name()) {
+            return Ok(value.clone());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(undefined(name)),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value<'src>) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.name()) {
+            *slot = value;
+            return Ok(());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(undefined(name)),
+        }
+    }
+
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value<'src>, RuntimeError> {
+        if distance == 0 {
+            return self
+                .values
+                .get(&name.name())
+                .cloned()
+                .ok_or_else(|| undefined(name));
+        }
+        self.ancestor(distance).borrow().get_at(0, name)
+    }
+
+    pub fn assign_at(
+        &mut self,
+        distance: usize,
+        name: &Token,
+        value: Value<'src>,
+    ) -> Result<(), RuntimeError> {
+        if distance == 0 {
+            return match self.values.get_mut(&name.name()) {
+                Some(slot) => {
+                    *slot = value;
+                    Ok(())
+                }
+                None => Err(undefined(name)),
+            };
+        }
+        self.ancestor(distance)
+            .borrow_mut()
+            .assign_at(0, name, value)
+    }
+
+    fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment<'src>>> {
+        let mut environment = self
+            .enclosing
+            .clone()
+            .expect("resolved scope depth exceeds environment chain");
+        for _ in 1..distance {
+            let enclosing = environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("resolved scope depth exceeds environment chain");
+            environment = enclosing;
+        }
+        environment
+    }
+}
+
+fn undefined(name: &Token) -> RuntimeError {
+    RuntimeError::new(
+        name,
+        ErrorCode::UndefinedVariable,
+        &format!("Undefined variable '{}'.", name.lexeme),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{Span, TokenType};
+
+    fn ident(name: &str) -> Token<'_> {
+        Token {
+            kind: TokenType::Identifier,
+            lexeme: name,
+            line: 1,
+            column: 1,
+            span: Span::new(0, name.len()),
+            literal: None,
+            symbol: Some(Symbol::intern(name)),
+        }
+    }
+
+    #[test]
+    fn shadowing_and_assignment() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Num(1.0));
+        outer
+            .borrow_mut()
+            .define(Symbol::intern("b"), Value::Num(2.0));
+
+        let mut inner = Environment::with_enclosing(outer.clone());
+        inner.define(Symbol::intern("a"), Value::Nil);
+        inner.assign(&ident("b"), Value::Bool(true)).unwrap();
+
+        assert_eq!(inner.get(&ident("a")), Ok(Value::Nil));
+        assert_eq!(outer.borrow().get(&ident("a")), Ok(Value::Num(1.0)));
+        assert_eq!(outer.borrow().get(&ident("b")), Ok(Value::Bool(true)));
+        assert!(inner.assign(&ident("c"), Value::Nil).is_err());
+    }
+
+    #[test]
+    fn resolved_access() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Num(1.0));
+        let middle = Rc::new(RefCell::new(Environment::with_enclosing(outer.clone())));
+        middle
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Num(2.0));
+        let mut inner = Environment::with_enclosing(middle);
+
+        assert_eq!(inner.get_at(2, &ident("a")), Ok(Value::Num(1.0)));
+        assert_eq!(inner.get_at(1, &ident("a")), Ok(Value::Num(2.0)));
+        inner.assign_at(2, &ident("a"), Value::Nil).unwrap();
+        assert_eq!(outer.borrow().get(&ident("a")), Ok(Value::Nil));
+        assert!(inner.get_at(0, &ident("a")).is_err());
+    }
+}
@@ -0,0 +1,689 @@
+use std::fmt;
+
+// Every error rox can report. Codes are part of the public interface: once
+// published a code is never renumbered or reused, only retired.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorCode {
+    // Scanner
+    UnterminatedString,
+    UnexpectedCharacter,
+    InvalidEscape,
+    InvalidUnicodeEscape,
+    UnterminatedInterpolation,
+    InvalidNumber,
+    UnterminatedComment,
+
+    // Parser
+    ExpectedExpression,
+    MissingSemicolon,
+    ExpectedName,
+    UnclosedDelimiter,
+    ExpectedToken,
+    TooManyArguments,
+    InvalidAssignmentTarget,
+    UnsupportedSyntax,
+
+    // Resolver
+    DuplicateVariable,
+    ReadInOwnInitializer,
+    TopLevelReturn,
+    ReturnFromInitializer,
+    InheritFromSelf,
+    ThisOutsideClass,
+    SuperOutsideClass,
+    SuperWithoutSuperclass,
+
+    // Runtime
+    UndefinedVariable,
+    UndefinedProperty,
+    NumberOperands,
+    AddOperands,
+    NotCallable,
+    ArityMismatch,
+    NotAnInstance,
+    SuperclassNotClass,
+    StackOverflow,
+    InvalidBytecode,
+}
+
+use ErrorCode::*;
+
+const ALL: &[ErrorCode] = &[
+    UnterminatedString,
+    UnexpectedCharacter,
+    ExpectedExpression,
+    MissingSemicolon,
+    ExpectedName,
+    UnclosedDelimiter,
+    ExpectedToken,
+    TooManyArguments,
+    InvalidAssignmentTarget,
+    InvalidNumber,
+    DuplicateVariable,
+    ReadInOwnInitializer,
+    TopLevelReturn,
+    ReturnFromInitializer,
+    InheritFromSelf,
+    ThisOutsideClass,
+    SuperOutsideClass,
+    SuperWithoutSuperclass,
+    UndefinedVariable,
+    UndefinedProperty,
+    NumberOperands,
+    AddOperands,
+    NotCallable,
+    ArityMismatch,
+    NotAnInstance,
+    SuperclassNotClass,
+    InvalidEscape,
+    InvalidUnicodeEscape,
+    UnterminatedInterpolation,
+    UnterminatedComment,
+    StackOverflow,
+    UnsupportedSyntax,
+    InvalidBytecode,
+];
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnterminatedString => "E0001",
+            UnexpectedCharacter => "E0002",
+            ExpectedExpression => "E0003",
+            MissingSemicolon => "E0004",
+            ExpectedName => "E0005",
+            UnclosedDelimiter => "E0006",
+            ExpectedToken => "E0007",
+            TooManyArguments => "E0008",
+            InvalidAssignmentTarget => "E0009",
+            InvalidNumber => "E0010",
+            DuplicateVariable => "E0011",
+            ReadInOwnInitializer => "E0012",
+            TopLevelReturn => "E0013",
+            ReturnFromInitializer => "E0014",
+            InheritFromSelf => "E0015",
+            ThisOutsideClass => "E0016",
+            SuperOutsideClass => "E0017",
+            SuperWithoutSuperclass => "E0018",
+            UndefinedVariable => "E0019",
+            UndefinedProperty => "E0020",
+            NumberOperands => "E0021",
+            AddOperands => "E0022",
+            NotCallable => "E0023",
+            ArityMismatch => "E0024",
+            NotAnInstance => "E0025",
+            SuperclassNotClass => "E0026",
+            InvalidEscape => "E0027",
+            InvalidUnicodeEscape => "E0028",
+            UnterminatedInterpolation => "E0029",
+            UnterminatedComment => "E0030",
+            StackOverflow => "E0031",
+            UnsupportedSyntax => "E0032",
+            InvalidBytecode => "E0033",
+        }
+    }
+
+    // Accepts the code with or without its leading `E`
+    pub fn parse(code: &str) -> Option<Self> {
+        let code = code.strip_prefix(['E', 'e']).unwrap_or(code);
+        ALL.iter().copied().find(|c| &c.as_str()[1..] == code)
+    }
+
+    // Long-form help shown by `rox --explain`
+    pub fn explanation(self) -> &'static str {
+        match self {
+            UnterminatedString => {
+                "\
+A string literal was opened with `\"` but the file ended before it was closed.
+
+Erroneous code example:
+
+    print \"hello;
+
+Strings may span several lines, so the scanner keeps reading until it finds
+the closing quote. Add the missing `\"`:
+
+    print \"hello\";
+"
+            }
+            UnexpectedCharacter => {
+                "\
+The source contains a character that cannot start any Lox token.
+
+Erroneous code example:
+
+    var total = 2 # 3;
+
+Lox only uses ASCII punctuation for its operators; characters such as `#`,
+`@`, `$` or `%` are not part of the language. Remove the character or replace
+it with a valid operator:
+
+    var total = 2 * 3;
+"
+            }
+            InvalidEscape => {
+                "\
+A backslash in a string literal was followed by a character that does not
+form an escape sequence.
+
+Erroneous code example:
+
+    print \"C:\\data\";
+
+The supported escapes are `\\n`, `\\r`, `\\t`, `\\0`, `\\\"`, `\\\\`, `\\$` and
+`\\u{...}`. To include a literal backslash, escape it:
+
+    print \"C:\\\\data\";
+"
+            }
+            InvalidUnicodeEscape => {
+                "\
+A `\\u` escape in a string literal was malformed.
+
+Erroneous code example:
+
+    print \"\\u1F600\";
+
+Unicode escapes put 1 to 6 hexadecimal digits between braces, and the number
+must be a unicode scalar value (at most 10FFFF and not a surrogate):
+
+    print \"\\u{1F600}\";
+"
+            }
+            UnterminatedInterpolation => {
+                "\
+An expression embedded in a string with `${` was never closed with `}`.
+
+Erroneous code example:
+
+    print \"Hello, ${name!\";
+
+Everything after `${` is scanned as code until the matching `}`, so the rest
+of the file was swallowed. Close the interpolation:
+
+    print \"Hello, ${name}!\";
+
+To write a literal `${` in a string, escape the dollar sign: `\\${`.
+"
+            }
+            ExpectedExpression => {
+                "\
+The parser needed an expression (a value, variable, call, ...) but found
+something else.
+
+Erroneous code example:
+
+    var a = 1 + ;
+
+A binary operator needs an operand on both sides. Supply the missing
+expression:
+
+    var a = 1 + 2;
+"
+            }
+            MissingSemicolon => {
+                "\
+A statement was not terminated with `;`.
+
+Erroneous code example:
+
+    var a = 1
+    print a;
+
+Every expression statement, `print`, `var` and `return` statement ends with a
+semicolon. Add it:
+
+    var a = 1;
+    print a;
+"
+            }
+            ExpectedName => {
+                "\
+An identifier was expected, for instance after `var`, `fun`, `class` or `.`.
+
+Erroneous code example:
+
+    var 1 = 2;
+
+Names must start with a letter or `_` and cannot be keywords. Use a valid
+identifier:
+
+    var one = 2;
+"
+            }
+            UnclosedDelimiter => {
+                "\
+A `(` or `{` was opened but the matching `)` or `}` was not found where it
+was expected.
+
+Erroneous code example:
+
+    print (1 + 2;
+
+Close the delimiter before continuing:
+
+    print (1 + 2);
+"
+            }
+            ExpectedToken => {
+                "\
+A specific piece of punctuation required by the grammar is missing, such as
+the `(` after `if` or the `{` before a function body.
+
+Erroneous code example:
+
+    if a > 1 print a;
+
+Conditions of `if`, `while` and `for` are always parenthesized:
+
+    if (a > 1) print a;
+"
+            }
+            TooManyArguments => {
+                "\
+A function declaration or call has more than 255 parameters or arguments.
+
+Erroneous code example:
+
+    fun f(a1, a2, a3, ..., a256) {}
+
+Rox limits calls to 255 arguments. Group related values into an instance and
+pass that instead.
+"
+            }
+            InvalidAssignmentTarget => {
+                "\
+The left-hand side of `=` is not something that can be assigned to.
+
+Erroneous code example:
+
+    1 + 2 = 3;
+
+Only variables and instance fields can be assigned:
+
+    var a = 3;
+    point.x = 3;
+"
+            }
+            UnterminatedComment => {
+                "\
+A `/*` block comment was never closed with `*/`.
+
+Erroneous code example:
+
+    /* disabled for now
+    print \"hello\";
+
+Block comments nest, so every `/*` inside a comment needs its own `*/`:
+
+    /* disabled for now /* inner */
+    print \"hello\"; */
+"
+            }
+            InvalidNumber => {
+                "\
+A number literal is malformed.
+
+Erroneous code examples:
+
+    var mask = 0b1021;
+    var big = 1_000_;
+    var small = 1.5e;
+    var color = 0x;
+
+Number literals are decimal (`42`, `1.5`, `1.5e-3`) or use a prefix for
+hexadecimal (`0xFF`), octal (`0o17`) or binary (`0b1010`). Every digit must
+be valid for the literal's base, `_` separators must sit between digits, and
+an exponent needs at least one digit:
+
+    var mask = 0b1011;
+    var big = 1_000;
+    var small = 1.5e-3;
+    var color = 0xFF;
+"
+            }
+            DuplicateVariable => {
+                "\
+Two variables with the same name were declared in the same local scope.
+
+Erroneous code example:
+
+    fun f() {
+        var a = 1;
+        var a = 2;
+    }
+
+Redeclaring a local is almost always a mistake, so it is rejected. Assign to
+the existing variable, or introduce a new block to shadow it on purpose:
+
+    fun f() {
+        var a = 1;
+        a = 2;
+    }
+"
+            }
+            ReadInOwnInitializer => {
+                "\
+A local variable was used inside its own initializer.
+
+Erroneous code example:
+
+    var a = 1;
+    {
+        var a = a + 1;
+    }
+
+The inner `a` is not usable until its initializer has finished, so this does
+not refer to the outer `a`. Pick a different name for the new variable:
+
+    var a = 1;
+    {
+        var b = a + 1;
+    }
+"
+            }
+            TopLevelReturn => {
+                "\
+A `return` statement appeared outside of any function.
+
+Erroneous code example:
+
+    return 1;
+
+`return` only makes sense inside a function or method body. Remove it, or
+move the code into a function.
+"
+            }
+            ReturnFromInitializer => {
+                "\
+An `init` method tried to return a value.
+
+Erroneous code example:
+
+    class Point {
+        init(x) {
+            this.x = x;
+            return x;
+        }
+    }
+
+Initializers always return the new instance. Use a bare `return;` to exit
+early:
+
+    class Point {
+        init(x) {
+            this.x = x;
+            return;
+        }
+    }
+"
+            }
+            InheritFromSelf => {
+                "\
+A class named itself as its own superclass.
+
+Erroneous code example:
+
+    class A < A {}
+
+Inherit from a different, previously declared class:
+
+    class Base {}
+    class A < Base {}
+"
+            }
+            ThisOutsideClass => {
+                "\
+`this` was used outside of a method.
+
+Erroneous code example:
+
+    fun f() {
+        print this;
+    }
+
+`this` refers to the instance a method was called on, so it only exists
+inside class bodies. Pass the object as a parameter instead:
+
+    fun f(object) {
+        print object;
+    }
+"
+            }
+            SuperOutsideClass => {
+                "\
+`super` was used outside of a method.
+
+Erroneous code example:
+
+    super.greet();
+
+`super` looks up methods on the superclass of the enclosing class. It can
+only be used inside the methods of a class that has a superclass.
+"
+            }
+            SuperWithoutSuperclass => {
+                "\
+`super` was used in a class that does not inherit from anything.
+
+Erroneous code example:
+
+    class A {
+        greet() {
+            super.greet();
+        }
+    }
+
+Either declare a superclass or call the method directly:
+
+    class Base {
+        greet() {}
+    }
+    class A < Base {
+        greet() {
+            super.greet();
+        }
+    }
+"
+            }
+            UndefinedVariable => {
+                "\
+A variable was read or assigned before it was declared.
+
+Erroneous code example:
+
+    print count;
+
+Declare the variable with `var` first:
+
+    var count = 0;
+    print count;
+"
+            }
+            UndefinedProperty => {
+                "\
+An instance has neither a field nor a method with the requested name.
+
+Erroneous code example:
+
+    class Point {}
+    print Point().x;
+
+Fields only exist once they have been assigned, typically in `init`:
+
+    class Point {
+        init() {
+            this.x = 0;
+        }
+    }
+    print Point().x;
+"
+            }
+            NumberOperands => {
+                "\
+An arithmetic or comparison operator was applied to a value that is not a
+number.
+
+Erroneous code example:
+
+    print \"3\" * 2;
+
+Operators such as `-`, `*`, `/`, `<` and `>=` only work on numbers. Make sure
+both operands are numbers:
+
+    print 3 * 2;
+"
+            }
+            AddOperands => {
+                "\
+`+` was applied to a mix of values it cannot combine.
+
+Erroneous code example:
+
+    print \"total: \" + 3;
+
+`+` adds two numbers or concatenates two strings, but never mixes the two.
+Convert both sides to the same type:
+
+    print \"total: \" + \"3\";
+"
+            }
+            NotCallable => {
+                "\
+Something other than a function or class was called.
+
+Erroneous code example:
+
+    var greeting = \"hi\";
+    greeting();
+
+Only functions, methods and classes can be followed by `(...)`.
+"
+            }
+            ArityMismatch => {
+                "\
+A function was called with the wrong number of arguments.
+
+Erroneous code example:
+
+    fun add(a, b) {
+        return a + b;
+    }
+    add(1);
+
+Pass exactly as many arguments as the function declares parameters:
+
+    add(1, 2);
+"
+            }
+            NotAnInstance => {
+                "\
+A property was read or written on a value that is not an instance.
+
+Erroneous code example:
+
+    var n = 1;
+    n.size = 2;
+
+Only instances of classes have fields. Create an instance first:
+
+    class Box {}
+    var b = Box();
+    b.size = 2;
+"
+            }
+            SuperclassNotClass => {
+                "\
+The expression after `<` in a class declaration did not evaluate to a class.
+
+Erroneous code example:
+
+    var Base = \"not a class\";
+    class A < Base {}
+
+Inherit from a class:
+
+    class Base {}
+    class A < Base {}
+"
+            }
+            StackOverflow => {
+                "\
+Functions called each other so deeply that the call stack ran out of room.
+
+Erroneous code example:
+
+    fun countdown(n) {
+        return countdown(n - 1);
+    }
+    countdown(10);
+
+This usually means a recursive function is missing the case that stops the
+recursion:
+
+    fun countdown(n) {
+        if (n <= 0) return 0;
+        return countdown(n - 1);
+    }
+"
+            }
+            UnsupportedSyntax => {
+                "\
+A statement starts with a keyword from an enabled extension that the parser
+has no support for yet.
+
+Erroneous code example, run with `--enable=loop-control`:
+
+    while (true) {
+        break;
+    }
+
+The extension's keywords are reserved so that programs can be written against
+them, but statements using them can't be parsed or run. Until they are
+supported, rewrite the statement without the extension:
+
+    var running = true;
+    while (running) {
+        running = false;
+    }
+"
+            }
+            InvalidBytecode => {
+                "\
+A precompiled `.loxc` file contains code that the compiler never produces,
+such as defining a method on something that isn't a class.
+
+The file passed its checksum, so it was most likely edited or generated by
+another tool rather than corrupted by accident. Recompile it from its source:
+
+    rox compile script.lox
+    rox run script.loxc
+"
+            }
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_unique_and_parse() {
+        for (i, code) in ALL.iter().enumerate() {
+            assert_eq!(code.as_str(), format!("E{:04}", i + 1));
+            assert_eq!(ErrorCode::parse(code.as_str()), Some(*code));
+            assert!(!code.explanation().is_empty());
+        }
+        assert_eq!(ErrorCode::parse("0001"), Some(UnterminatedString));
+        assert_eq!(ErrorCode::parse("e0019"), Some(UndefinedVariable));
+        assert_eq!(ErrorCode::parse("E9999"), None);
+        assert_eq!(ErrorCode::parse(""), None);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::class::LoxInstance;
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, RuntimeError, Unwind, Value};
+use crate::parser::FunctionDecl;
+use crate::symbol;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub trait Callable<'src>: fmt::Display {
+    fn arity(&self) -> usize;
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter<'src>,
+        arguments: Vec<Value<'src>>,
+    ) -> Result<Value<'src>, RuntimeError>;
+}
+
+pub struct LoxFunction<'src> {
+    declaration: Rc<FunctionDecl<'src>>,
+    closure: Rc<RefCell<Environment<'src>>>,
+    is_initializer: bool,
+}
+
+impl<'src> LoxFunction<'src> {
+    pub fn new(
+        declaration: Rc<FunctionDecl<'src>>,
+        closure: Rc<RefCell<Environment<'src>>>,
+        is_initializer: bool,
+    ) -> Self {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance<'src>>>) -> LoxFunction<'src> {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        environment.define(*symbol::THIS, Value::Instance(instance));
+        LoxFunction::new(
+            self.declaration.clone(),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+
+    fn this(&self) -> Value<'src> {
+        self.closure
+            .borrow()
+            .get_local(*symbol::THIS)
+            .expect("initializer is bound to an instance")
+    }
+}
+
+impl<'src> Callable<'src> for LoxFunction<'src> {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter<'src>,
+        arguments: Vec<Value<'src>>,
+    ) -> Result<Value<'src>, RuntimeError> {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.name(), argument);
+        }
+        let environment = Rc::new(RefCell::new(environment));
+        match interpreter.execute_block(&self.declaration.body, environment) {
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => Ok(self.this()),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(e)) => Err(e),
+        }
+    }
+}
+
+impl fmt::Display for LoxFunction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
+
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: for<'src> fn(&[Value<'src>]) -> Value<'src>,
+}
+
+impl<'src> Callable<'src> for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        self: Rc<Self>,
+        _: &mut Interpreter<'src>,
+        arguments: Vec<Value<'src>>,
+    ) -> Result<Value<'src>, RuntimeError> {
+        Ok((self.function)(&arguments))
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+pub fn natives() -> Vec<NativeFunction> {
+    vec![NativeFunction {
+        name: "clock",
+        arity: 0,
+        function: |_| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            Value::Num(now.as_secs_f64())
+        },
+    }]
+}
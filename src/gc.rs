@@ -0,0 +1,356 @@
+use crate::object::{BoundMethod, Class, Closure, Instance, Upvalue, Value};
+use std::fmt;
+use std::marker::PhantomData;
+
+// Collections never run while fewer objects than this are alive
+const MIN_THRESHOLD: usize = 1024;
+// After a collection, the next one runs once the live object count has grown
+// by this factor
+const GROWTH_FACTOR: usize = 2;
+
+// A handle to an object on the heap. Handles stay valid as long as the object
+// is reachable from the roots passed to the collector.
+pub struct Gc<T> {
+    index: u32,
+    marker: PhantomData<T>,
+}
+
+impl<T> Gc<T> {
+    fn new(index: u32) -> Self {
+        Gc {
+            index,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Gc<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Gc<T> {}
+
+impl<T> PartialEq for Gc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> fmt::Debug for Gc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Gc({})", self.index)
+    }
+}
+
+// Strings and natives are reference counted instead, since they can't be part
+// of a cycle
+pub enum Object {
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+}
+
+pub trait HeapObject: Sized {
+    fn into_object(self) -> Object;
+    fn from_object(object: &Object) -> Option<&Self>;
+    fn from_object_mut(object: &mut Object) -> Option<&mut Self>;
+}
+
+impl HeapObject for Closure {
+    fn into_object(self) -> Object {
+        Object::Closure(self)
+    }
+
+    fn from_object(object: &Object) -> Option<&Self> {
+        match object {
+            Object::Closure(closure) => Some(closure),
+            _ => None,
+        }
+    }
+
+    fn from_object_mut(object: &mut Object) -> Option<&mut Self> {
+        match object {
+            Object::Closure(closure) => Some(closure),
+            _ => None,
+        }
+    }
+}
+
+impl HeapObject for Upvalue {
+    fn into_object(self) -> Object {
+        Object::Upvalue(self)
+    }
+
+    fn from_object(object: &Object) -> Option<&Self> {
+        match object {
+            Object::Upvalue(upvalue) => Some(upvalue),
+            _ => None,
+        }
+    }
+
+    fn from_object_mut(object: &mut Object) -> Option<&mut Self> {
+        match object {
+            Object::Upvalue(upvalue) => Some(upvalue),
+            _ => None,
+        }
+    }
+}
+
+impl HeapObject for Class {
+    fn into_object(self) -> Object {
+        Object::Class(self)
+    }
+
+    fn from_object(object: &Object) -> Option<&Self> {
+        match object {
+            Object::Class(class) => Some(class),
+            _ => None,
+        }
+    }
+
+    fn from_object_mut(object: &mut Object) -> Option<&mut Self> {
+        match object {
+            Object::Class(class) => Some(class),
+            _ => None,
+        }
+    }
+}
+
+impl HeapObject for Instance {
+    fn into_object(self) -> Object {
+        Object::Instance(self)
+    }
+
+    fn from_object(object: &Object) -> Option<&Self> {
+        match object {
+            Object::Instance(instance) => Some(instance),
+            _ => None,
+        }
+    }
+
+    fn from_object_mut(object: &mut Object) -> Option<&mut Self> {
+        match object {
+            Object::Instance(instance) => Some(instance),
+            _ => None,
+        }
+    }
+}
+
+impl HeapObject for BoundMethod {
+    fn into_object(self) -> Object {
+        Object::BoundMethod(self)
+    }
+
+    fn from_object(object: &Object) -> Option<&Self> {
+        match object {
+            Object::BoundMethod(bound) => Some(bound),
+            _ => None,
+        }
+    }
+
+    fn from_object_mut(object: &mut Object) -> Option<&mut Self> {
+        match object {
+            Object::BoundMethod(bound) => Some(bound),
+            _ => None,
+        }
+    }
+}
+
+// Heap index of the object a value refers to, if any
+fn value_index(value: &Value) -> Option<u32> {
+    match value {
+        Value::Closure(closure) => Some(closure.index),
+        Value::Class(class) => Some(class.index),
+        Value::Instance(instance) => Some(instance.index),
+        Value::BoundMethod(bound) => Some(bound.index),
+        Value::Nil | Value::Bool(_) | Value::Num(_) | Value::Str(_) | Value::Native(_) => None,
+    }
+}
+
+// A mark-and-sweep heap. The owner marks its roots and calls `collect`, which
+// traces from them with a gray worklist and frees everything left unmarked.
+pub struct Heap {
+    // Freed slots are `None` until they are reused
+    objects: Vec<Option<Object>>,
+    marked: Vec<bool>,
+    free: Vec<u32>,
+    // Marked objects whose children haven't been marked yet
+    gray: Vec<u32>,
+    live: usize,
+    next_gc: usize,
+    // Collect before every allocation, to shake out missing roots
+    stress: bool,
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Heap {
+            objects: Vec::new(),
+            marked: Vec::new(),
+            free: Vec::new(),
+            gray: Vec::new(),
+            live: 0,
+            next_gc: MIN_THRESHOLD,
+            stress: false,
+        }
+    }
+
+    pub fn with_stress(mut self, stress: bool) -> Self {
+        self.stress = stress;
+        self
+    }
+
+    pub fn live(&self) -> usize {
+        self.live
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.stress || self.live >= self.next_gc
+    }
+
+    pub fn alloc<T: HeapObject>(&mut self, object: T) -> Gc<T> {
+        let object = Some(object.into_object());
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.objects[index as usize] = object;
+                index
+            }
+            None => {
+                self.objects.push(object);
+                self.marked.push(false);
+                (self.objects.len() - 1) as u32
+            }
+        };
+        self.live += 1;
+        Gc::new(index)
+    }
+
+    pub fn get<T: HeapObject>(&self, gc: Gc<T>) -> &T {
+        self.objects[gc.index as usize]
+            .as_ref()
+            .and_then(T::from_object)
+            .expect("use of a collected object")
+    }
+
+    pub fn get_mut<T: HeapObject>(&mut self, gc: Gc<T>) -> &mut T {
+        self.objects[gc.index as usize]
+            .as_mut()
+            .and_then(T::from_object_mut)
+            .expect("use of a collected object")
+    }
+
+    pub fn mark<T>(&mut self, gc: Gc<T>) {
+        mark(&mut self.marked, &mut self.gray, gc.index);
+    }
+
+    pub fn mark_value(&mut self, value: &Value) {
+        if let Some(index) = value_index(value) {
+            mark(&mut self.marked, &mut self.gray, index);
+        }
+    }
+
+    // Frees every object not reachable from the marked roots
+    pub fn collect(&mut self) {
+        while let Some(index) = self.gray.pop() {
+            self.blacken(index);
+        }
+        for (index, object) in self.objects.iter_mut().enumerate() {
+            if self.marked[index] {
+                self.marked[index] = false;
+            } else if object.is_some() {
+                *object = None;
+                self.free.push(index as u32);
+                self.live -= 1;
+            }
+        }
+        self.next_gc = (self.live * GROWTH_FACTOR).max(MIN_THRESHOLD);
+    }
+
+    // Marks the children of a gray object
+    fn blacken(&mut self, index: u32) {
+        let Heap {
+            objects,
+            marked,
+            gray,
+            ..
+        } = self;
+        let mut mark = |index| mark(marked, gray, index);
+        match objects[index as usize].as_ref().unwrap() {
+            Object::Closure(closure) => {
+                for upvalue in &closure.upvalues {
+                    mark(upvalue.index);
+                }
+            }
+            // Open upvalues point into the stack, which is a root
+            Object::Upvalue(Upvalue::Open(_)) => {}
+            Object::Upvalue(Upvalue::Closed(value)) => {
+                value_index(value).into_iter().for_each(mark)
+            }
+            Object::Class(class) => {
+                for method in class.methods.values() {
+                    mark(method.index);
+                }
+            }
+            Object::Instance(instance) => {
+                mark(instance.class.index);
+                instance
+                    .fields
+                    .values()
+                    .filter_map(value_index)
+                    .for_each(mark);
+            }
+            Object::BoundMethod(bound) => {
+                mark(bound.method.index);
+                value_index(&bound.receiver).into_iter().for_each(mark);
+            }
+        }
+    }
+}
+
+fn mark(marked: &mut [bool], gray: &mut Vec<u32>, index: u32) {
+    if !marked[index as usize] {
+        marked[index as usize] = true;
+        gray.push(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol::Symbol;
+
+    #[test]
+    fn collect() {
+        let mut heap = Heap::new();
+        let upvalues: Vec<_> = (0..3000).map(|i| heap.alloc(Upvalue::Open(i))).collect();
+        assert!(heap.should_collect());
+
+        // Closed upvalues keep what they hold alive
+        let class = heap.alloc(Class {
+            name: Symbol::intern("A"),
+            methods: Default::default(),
+        });
+        *heap.get_mut(upvalues[0]) = Upvalue::Closed(Value::Class(class));
+        for &upvalue in &upvalues[..1500] {
+            heap.mark(upvalue);
+        }
+        heap.collect();
+        assert_eq!(heap.live(), 1501);
+        assert_eq!(heap.get(class).name.as_str(), "A");
+        assert!(!heap.should_collect());
+
+        // The threshold adapts to the number of survivors
+        assert_eq!(heap.next_gc, 3002);
+        heap.collect();
+        assert_eq!(heap.live(), 0);
+        assert_eq!(heap.next_gc, MIN_THRESHOLD);
+
+        // Freed slots get reused
+        heap.alloc(Upvalue::Open(0));
+        assert_eq!(heap.objects.len(), 3001);
+    }
+}
@@ -0,0 +1,813 @@
+use crate::class::{LoxClass, LoxInstance};
+use crate::diagnostics::{Diagnostic, ToDiagnostic};
+use crate::environment::Environment;
+use crate::error_code::ErrorCode;
+use crate::function::{self, Callable, LoxFunction};
+use crate::parser::{Expression, FunctionDecl, Literal, Stmt};
+use crate::scanner::{Span, Token, TokenType};
+use crate::symbol::{self, LoxString, Symbol};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// Deepest call nesting allowed before reporting a stack overflow, matching the
+// VM's frame limit
+const MAX_CALL_DEPTH: usize = 1024;
+// Native stack needed to reach that depth, since every Lox call recurses
+// through several Rust frames
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Clone)]
+pub enum Value<'src> {
+    Nil,
+    Bool(bool),
+    Num(f64),
+    Str(LoxString),
+    Callable(Rc<dyn Callable<'src> + 'src>),
+    Class(Rc<LoxClass<'src>>),
+    Instance(Rc<RefCell<LoxInstance<'src>>>),
+}
+
+impl Value<'_> {
+    fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Num(n) => write!(f, "{n}"),
+            Value::Str(s) => write!(f, "{s}"),
+            Value::Callable(c) => write!(f, "{c}"),
+            Value::Class(c) => write!(f, "{c}"),
+            Value::Instance(i) => write!(f, "{}", i.borrow()),
+        }
+    }
+}
+
+impl fmt::Debug for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "{s:?}"),
+            _ => write!(f, "{self}"),
+        }
+    }
+}
+
+impl PartialEq for Value<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Num(a), Value::Num(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Callable(a), Value::Callable(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct RuntimeError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub line: i32,
+    pub span: Span,
+}
+
+impl RuntimeError {
+    pub fn new(token: &Token, code: ErrorCode, message: &str) -> Self {
+        RuntimeError {
+            code,
+            message: message.to_owned(),
+            line: token.line,
+            span: token.span,
+        }
+    }
+
+    // Widens the error to cover a whole expression rather than one token
+    fn spanning(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.line)
+    }
+}
+
+impl ToDiagnostic for RuntimeError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(&self.message, self.span).with_code(self.code)
+    }
+}
+
+// Non-local exits out of statement execution
+pub enum Unwind<'src> {
+    Return(Value<'src>),
+    Error(RuntimeError),
+}
+
+impl From<RuntimeError> for Unwind<'_> {
+    fn from(e: RuntimeError) -> Self {
+        Unwind::Error(e)
+    }
+}
+
+pub struct Interpreter<'src> {
+    globals: Rc<RefCell<Environment<'src>>>,
+    environment: Rc<RefCell<Environment<'src>>>,
+    call_depth: usize,
+}
+
+impl<'src> Interpreter<'src> {
+    pub fn new() -> Self {
+        let mut globals = Environment::new();
+        for native in function::natives() {
+            globals.define(
+                Symbol::intern(native.name),
+                Value::Callable(Rc::new(native)),
+            );
+        }
+        let globals = Rc::new(RefCell::new(globals));
+        Interpreter {
+            globals: globals.clone(),
+            environment: globals,
+            call_depth: 0,
+        }
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt<'src>]) -> Result<(), RuntimeError> {
+        for stmt in statements {
+            match self.execute(stmt) {
+                Ok(()) => {}
+                Err(Unwind::Return(_)) => break,
+                Err(Unwind::Error(e)) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, stmt: &Stmt<'src>) -> Result<(), Unwind<'src>> {
+        match stmt {
+            Stmt::Expression(expr) => {
+                self.evaluate(expr)?;
+            }
+            Stmt::Print(expr) => {
+                let value = self.evaluate(expr)?;
+                println!("{value}");
+            }
+            Stmt::Var { name, initializer } => {
+                let value = match initializer {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::Nil,
+                };
+                self.environment.borrow_mut().define(name.name(), value);
+            }
+            Stmt::Block(statements) => {
+                let environment = Environment::with_enclosing(self.environment.clone());
+                self.execute_block(statements, Rc::new(RefCell::new(environment)))?;
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.evaluate(condition)?.is_truthy() {
+                    self.execute(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)?;
+                }
+            }
+            Stmt::While { condition, body } => {
+                while self.evaluate(condition)?.is_truthy() {
+                    self.execute(body)?;
+                }
+            }
+            Stmt::Function(declaration) => {
+                let function =
+                    LoxFunction::new(declaration.clone(), self.environment.clone(), false);
+                self.environment
+                    .borrow_mut()
+                    .define(declaration.name.name(), Value::Callable(Rc::new(function)));
+            }
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::Nil,
+                };
+                return Err(Unwind::Return(value));
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => self.class_declaration(name, superclass.as_ref(), methods)?,
+        }
+        Ok(())
+    }
+
+    fn class_declaration(
+        &mut self,
+        name: &Token<'src>,
+        superclass: Option<&Expression<'src>>,
+        methods: &[Rc<FunctionDecl<'src>>],
+    ) -> Result<(), RuntimeError> {
+        let superclass = match superclass {
+            Some(expr) => match self.evaluate(expr)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    let Expression::Variable {
+                        name: superclass_name,
+                        ..
+                    } = expr
+                    else {
+                        unreachable!("superclass is always a variable");
+                    };
+                    return Err(RuntimeError::new(
+                        superclass_name,
+                        ErrorCode::SuperclassNotClass,
+                        "Superclass must be a class.",
+                    ));
+                }
+            },
+            None => None,
+        };
+        self.environment
+            .borrow_mut()
+            .define(name.name(), Value::Nil);
+
+        // Methods of a subclass close over an extra scope binding `super`
+        let mut closure = self.environment.clone();
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::with_enclosing(closure);
+            environment.define(*symbol::SUPER, Value::Class(superclass.clone()));
+            closure = Rc::new(RefCell::new(environment));
+        }
+        let methods = methods
+            .iter()
+            .map(|method| {
+                let function = LoxFunction::new(
+                    method.clone(),
+                    closure.clone(),
+                    method.name.name() == *symbol::INIT,
+                );
+                (method.name.name(), Rc::new(function))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let class = LoxClass::new(name.lexeme, superclass, methods);
+        self.environment
+            .borrow_mut()
+            .assign(name, Value::Class(Rc::new(class)))
+    }
+
+    pub fn execute_block(
+        &mut self,
+        statements: &[Stmt<'src>],
+        environment: Rc<RefCell<Environment<'src>>>,
+    ) -> Result<(), Unwind<'src>> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = statements.iter().try_for_each(|stmt| self.execute(stmt));
+        self.environment = previous;
+        result
+    }
+
+    pub fn evaluate(&mut self, expr: &Expression<'src>) -> Result<Value<'src>, RuntimeError> {
+        match expr {
+            Expression::Literal(lit, _) => Ok(match lit {
+                Literal::Num(n) => Value::Num(*n),
+                Literal::Str(s) => Value::Str((*s).into()),
+                Literal::Bool(b) => Value::Bool(*b),
+                Literal::Nil => Value::Nil,
+            }),
+            Expression::Unary { op, e } => {
+                let right = self.evaluate(e)?;
+                match op.kind {
+                    TokenType::Minus => match right {
+                        Value::Num(n) => Ok(Value::Num(-n)),
+                        _ => Err(RuntimeError::new(
+                            op,
+                            ErrorCode::NumberOperands,
+                            "Operand must be a number.",
+                        )
+                        .spanning(expr.span())),
+                    },
+                    TokenType::Not => Ok(Value::Bool(!right.is_truthy())),
+                    _ => unreachable!("invalid unary operator {:?}", op.kind),
+                }
+            }
+            Expression::Binary { e1, op, e2 } => {
+                let left = self.evaluate(e1)?;
+                let right = self.evaluate(e2)?;
+                binary(op, left, right).map_err(|e| e.spanning(expr.span()))
+            }
+            Expression::Logical { e1, op, e2 } => {
+                let left = self.evaluate(e1)?;
+                let short_circuit = match op.kind {
+                    TokenType::Or => left.is_truthy(),
+                    _ => !left.is_truthy(),
+                };
+                if short_circuit {
+                    Ok(left)
+                } else {
+                    self.evaluate(e2)
+                }
+            }
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let callee_span = callee.span();
+                let callee = self.evaluate(callee)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|arg| self.evaluate(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let function: Rc<dyn Callable<'src> + 'src> = match callee {
+                    Value::Callable(function) => function,
+                    Value::Class(class) => class,
+                    _ => {
+                        return Err(RuntimeError::new(
+                            paren,
+                            ErrorCode::NotCallable,
+                            "Can only call functions and classes.",
+                        )
+                        .spanning(callee_span))
+                    }
+                };
+                if arguments.len() != function.arity() {
+                    return Err(RuntimeError::new(
+                        paren,
+                        ErrorCode::ArityMismatch,
+                        &format!(
+                            "Expected {} arguments but got {}.",
+                            function.arity(),
+                            arguments.len()
+                        ),
+                    )
+                    .spanning(expr.span()));
+                }
+                if self.call_depth == MAX_CALL_DEPTH {
+                    return Err(RuntimeError::new(
+                        paren,
+                        ErrorCode::StackOverflow,
+                        "Stack overflow.",
+                    )
+                    .spanning(expr.span()));
+                }
+                self.call_depth += 1;
+                let result = function.call(self, arguments);
+                self.call_depth -= 1;
+                result
+            }
+            Expression::Get { object, name } => match self.evaluate(object)? {
+                Value::Instance(instance) => LoxInstance::get(&instance, name),
+                _ => Err(RuntimeError::new(
+                    name,
+                    ErrorCode::NotAnInstance,
+                    "Only instances have properties.",
+                )),
+            },
+            Expression::Set {
+                object,
+                name,
+                value,
+            } => {
+                let Value::Instance(instance) = self.evaluate(object)? else {
+                    return Err(RuntimeError::new(
+                        name,
+                        ErrorCode::NotAnInstance,
+                        "Only instances have fields.",
+                    ));
+                };
+                let value = self.evaluate(value)?;
+                instance.borrow_mut().set(name, value.clone());
+                Ok(value)
+            }
+            Expression::This { keyword, depth } => self.look_up_variable(keyword, depth.get()),
+            Expression::Super {
+                keyword,
+                method,
+                depth,
+            } => {
+                // `this` is bound in the scope just inside the one binding `super`
+                let distance = depth.get().expect("'super' is always resolved");
+                let Value::Class(superclass) =
+                    self.environment.borrow().get_at(distance, keyword)?
+                else {
+                    unreachable!("'super' is always bound to a class");
+                };
+                let this = Token {
+                    kind: TokenType::This,
+                    lexeme: "this",
+                    symbol: Some(*symbol::THIS),
+                    ..keyword.clone()
+                };
+                let Value::Instance(instance) =
+                    self.environment.borrow().get_at(distance - 1, &this)?
+                else {
+                    unreachable!("'this' is always bound to an instance");
+                };
+                match superclass.find_method(method.name()) {
+                    Some(function) => Ok(Value::Callable(Rc::new(function.bind(instance)))),
+                    None => Err(RuntimeError::new(
+                        method,
+                        ErrorCode::UndefinedProperty,
+                        &format!("Undefined property '{}'.", method.lexeme),
+                    )),
+                }
+            }
+            Expression::Interpolation(parts) => {
+                let mut s = String::new();
+                for part in parts {
+                    s += &self.evaluate(part)?.to_string();
+                }
+                Ok(Value::Str(s.into()))
+            }
+            Expression::Variable { name, depth } => self.look_up_variable(name, depth.get()),
+            Expression::Assign { name, value, depth } => {
+                let value = self.evaluate(value)?;
+                match depth.get() {
+                    Some(distance) => {
+                        self.environment
+                            .borrow_mut()
+                            .assign_at(distance, name, value.clone())?
+                    }
+                    None => self.globals.borrow_mut().assign(name, value.clone())?,
+                }
+                Ok(value)
+            }
+        }
+    }
+
+    fn look_up_variable(
+        &self,
+        name: &Token<'src>,
+        depth: Option<usize>,
+    ) -> Result<Value<'src>, RuntimeError> {
+        match depth {
+            Some(distance) => self.environment.borrow().get_at(distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+}
+
+fn binary<'src>(
+    op: &Token,
+    left: Value<'src>,
+    right: Value<'src>,
+) -> Result<Value<'src>, RuntimeError> {
+    match op.kind {
+        TokenType::EqualEqual => return Ok(Value::Bool(left == right)),
+        TokenType::NotEqual => return Ok(Value::Bool(left != right)),
+        TokenType::Plus => {
+            return match (left, right) {
+                (Value::Num(a), Value::Num(b)) => Ok(Value::Num(a + b)),
+                (Value::Str(a), Value::Str(b)) => Ok(Value::Str(format!("{a}{b}").into())),
+                _ => Err(RuntimeError::new(
+                    op,
+                    ErrorCode::AddOperands,
+                    "Operands must be two numbers or two strings.",
+                )),
+            }
+        }
+        _ => {}
+    }
+
+    let (a, b) = match (left, right) {
+        (Value::Num(a), Value::Num(b)) => (a, b),
+        _ => {
+            return Err(RuntimeError::new(
+                op,
+                ErrorCode::NumberOperands,
+                "Operands must be numbers.",
+            ))
+        }
+    };
+    Ok(match op.kind {
+        TokenType::Minus => Value::Num(a - b),
+        TokenType::Times => Value::Num(a * b),
+        TokenType::Divide => Value::Num(a / b),
+        TokenType::Greater => Value::Bool(a > b),
+        TokenType::GreaterEqual => Value::Bool(a >= b),
+        TokenType::Less => Value::Bool(a < b),
+        TokenType::LessEqual => Value::Bool(a <= b),
+        _ => unreachable!("invalid binary operator {:?}", op.kind),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::resolver;
+    use crate::scanner::Scanner;
+    use std::thread;
+
+    fn eval(source: &str) -> Result<Value<'_>, RuntimeError> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let expr = Parser::new(tokens).parse().unwrap();
+        Interpreter::new().evaluate(&expr)
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("1 + 2 * 3 - 4 / 2"), Ok(Value::Num(5.0)));
+        assert_eq!(eval("-(1 + 2)"), Ok(Value::Num(-3.0)));
+    }
+
+    #[test]
+    fn comparison_and_equality() {
+        assert_eq!(eval("1 < 2 == true"), Ok(Value::Bool(true)));
+        assert_eq!(eval("1 == \"1\""), Ok(Value::Bool(false)));
+        assert_eq!(eval("nil == nil"), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn truthiness() {
+        assert_eq!(eval("!nil"), Ok(Value::Bool(true)));
+        assert_eq!(eval("!0"), Ok(Value::Bool(false)));
+        assert_eq!(eval("!!\"\""), Ok(Value::Bool(true)));
+    }
+
+    fn run<'src>(
+        interpreter: &mut Interpreter<'src>,
+        source: &'src str,
+    ) -> Result<(), RuntimeError> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        resolver::resolve(&program).unwrap();
+        interpreter.interpret(&program)
+    }
+
+    fn global<'src>(
+        interpreter: &mut Interpreter<'src>,
+        name: &'src str,
+    ) -> Result<Value<'src>, RuntimeError> {
+        let tokens = Scanner::new(name).scan_tokens().unwrap();
+        interpreter.evaluate(&Parser::new(tokens).parse().unwrap())
+    }
+
+    #[test]
+    fn globals() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(run(&mut interpreter, "var a = 1; var b = a + 2;"), Ok(()));
+        assert_eq!(global(&mut interpreter, "b"), Ok(Value::Num(3.0)));
+        assert_eq!(
+            global(&mut interpreter, "c"),
+            Err(RuntimeError {
+                code: ErrorCode::UndefinedVariable,
+                message: "Undefined variable 'c'.".to_owned(),
+                line: 1,
+                span: Span::new(0, 1)
+            })
+        );
+    }
+
+    #[test]
+    fn blocks_and_assignment() {
+        let mut interpreter = Interpreter::new();
+        let source = "var a = 1; var b = 2; { var a = 10; b = a = a + 1; } var c = a;";
+        assert_eq!(run(&mut interpreter, source), Ok(()));
+        assert_eq!(global(&mut interpreter, "b"), Ok(Value::Num(11.0)));
+        assert_eq!(global(&mut interpreter, "c"), Ok(Value::Num(1.0)));
+        assert_eq!(
+            run(&mut interpreter, "{ var d = 1; }\nd = 2;"),
+            Err(RuntimeError {
+                code: ErrorCode::UndefinedVariable,
+                message: "Undefined variable 'd'.".to_owned(),
+                line: 2,
+                span: Span::new(15, 16)
+            })
+        );
+    }
+
+    #[test]
+    fn control_flow() {
+        let mut interpreter = Interpreter::new();
+        let source = "
+            var sum = 0;
+            for (var i = 0; i < 5; i = i + 1) {
+                if (i == 2) sum = sum + 100; else sum = sum + i;
+            }
+            var n = 0;
+            while (n < 3) n = n + 1;
+        ";
+        assert_eq!(run(&mut interpreter, source), Ok(()));
+        assert_eq!(global(&mut interpreter, "sum"), Ok(Value::Num(108.0)));
+        assert_eq!(global(&mut interpreter, "n"), Ok(Value::Num(3.0)));
+    }
+
+    #[test]
+    fn logical_operators() {
+        assert_eq!(eval("nil or 2"), Ok(Value::Num(2.0)));
+        assert_eq!(eval("1 or undefined"), Ok(Value::Num(1.0)));
+        assert_eq!(eval("false and undefined"), Ok(Value::Bool(false)));
+        assert_eq!(eval("1 and nil"), Ok(Value::Nil));
+    }
+
+    #[test]
+    fn functions_and_closures() {
+        let mut interpreter = Interpreter::new();
+        let source = "
+            fun fib(n) {
+                if (n < 2) return n;
+                return fib(n - 1) + fib(n - 2);
+            }
+            fun makeCounter() {
+                var i = 0;
+                fun count() {
+                    i = i + 1;
+                    return i;
+                }
+                return count;
+            }
+            var counter = makeCounter();
+            counter();
+            var a = counter();
+            var b = fib(10);
+            fun noReturn() {}
+            var c = noReturn();
+        ";
+        assert_eq!(run(&mut interpreter, source), Ok(()));
+        assert_eq!(global(&mut interpreter, "a"), Ok(Value::Num(2.0)));
+        assert_eq!(global(&mut interpreter, "b"), Ok(Value::Num(55.0)));
+        assert_eq!(global(&mut interpreter, "c"), Ok(Value::Nil));
+        assert_eq!(
+            global(&mut interpreter, "fib").map(|f| f.to_string()),
+            Ok("<fn fib>".to_owned())
+        );
+        assert!(matches!(eval("clock()"), Ok(Value::Num(_))));
+    }
+
+    #[test]
+    fn closures_capture_declaration_scope() {
+        let mut interpreter = Interpreter::new();
+        let source = "
+            var a = 1;
+            var first;
+            var second;
+            {
+                fun show() { return a; }
+                first = show();
+                var a = 2;
+                second = show();
+            }
+        ";
+        assert_eq!(run(&mut interpreter, source), Ok(()));
+        assert_eq!(global(&mut interpreter, "first"), Ok(Value::Num(1.0)));
+        assert_eq!(global(&mut interpreter, "second"), Ok(Value::Num(1.0)));
+    }
+
+    #[test]
+    fn classes() {
+        let mut interpreter = Interpreter::new();
+        let source = "
+            class Point {
+                init(x, y) {
+                    this.x = x;
+                    this.y = y;
+                }
+                sum() { return this.x + this.y; }
+            }
+            class Point3 < Point {
+                init(x, y, z) {
+                    super.init(x, y);
+                    this.z = z;
+                }
+                sum() { return super.sum() + this.z; }
+            }
+            var p = Point3(1, 2, 3);
+            var sum = p.sum;
+            var total = sum();
+            var again = p.init(4, 5, 6) == p;
+        ";
+        assert_eq!(run(&mut interpreter, source), Ok(()));
+        assert_eq!(global(&mut interpreter, "total"), Ok(Value::Num(6.0)));
+        assert_eq!(global(&mut interpreter, "again"), Ok(Value::Bool(true)));
+        assert_eq!(global(&mut interpreter, "p.x + p.z"), Ok(Value::Num(10.0)));
+        assert_eq!(
+            global(&mut interpreter, "p").map(|p| p.to_string()),
+            Ok("Point3 instance".to_owned())
+        );
+        assert_eq!(
+            run(&mut interpreter, "p.missing;"),
+            Err(RuntimeError {
+                code: ErrorCode::UndefinedProperty,
+                message: "Undefined property 'missing'.".to_owned(),
+                line: 1,
+                span: Span::new(2, 9)
+            })
+        );
+        assert_eq!(
+            run(
+                &mut interpreter,
+                "var NotAClass = 1; class Oops < NotAClass {}"
+            ),
+            Err(RuntimeError {
+                code: ErrorCode::SuperclassNotClass,
+                message: "Superclass must be a class.".to_owned(),
+                line: 1,
+                span: Span::new(32, 41)
+            })
+        );
+    }
+
+    #[test]
+    fn interpolation() {
+        let mut interpreter = Interpreter::new();
+        let source = r#"
+            var name = "rox";
+            var items = 2;
+            var greeting = "Hello, ${name}! You have ${items + 1} items";
+            var nested = "${"[${items * 10}]"}${nil} ${true}";
+        "#;
+        assert_eq!(run(&mut interpreter, source), Ok(()));
+        assert_eq!(
+            global(&mut interpreter, "greeting"),
+            Ok(Value::Str("Hello, rox! You have 3 items".into()))
+        );
+        assert_eq!(
+            global(&mut interpreter, "nested"),
+            Ok(Value::Str("[20]nil true".into()))
+        );
+    }
+
+    #[test]
+    fn call_errors() {
+        assert_eq!(
+            eval("clock(1)"),
+            Err(RuntimeError {
+                code: ErrorCode::ArityMismatch,
+                message: "Expected 0 arguments but got 1.".to_owned(),
+                line: 1,
+                span: Span::new(0, 8)
+            })
+        );
+        assert_eq!(
+            eval("nil()"),
+            Err(RuntimeError {
+                code: ErrorCode::NotCallable,
+                message: "Can only call functions and classes.".to_owned(),
+                line: 1,
+                span: Span::new(0, 3)
+            })
+        );
+        // Test threads don't get enough stack for the full call depth
+        let (overflow, after) = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(|| {
+                let mut interpreter = Interpreter::new();
+                let source = "fun f(n) { return f(n + 1); }\nf(0);";
+                let overflow = run(&mut interpreter, source);
+                let deep = "fun g(n) { if (n > 0) return g(n - 1); return n; } g(1000);";
+                (overflow, run(&mut interpreter, deep))
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(
+            overflow,
+            Err(RuntimeError {
+                code: ErrorCode::StackOverflow,
+                message: "Stack overflow.".to_owned(),
+                line: 1,
+                span: Span::new(18, 26)
+            })
+        );
+        // The depth unwinds with the error, so the limit isn't hit any sooner
+        assert_eq!(after, Ok(()));
+    }
+
+    #[test]
+    fn type_errors() {
+        assert_eq!(
+            eval("1 + nil"),
+            Err(RuntimeError {
+                code: ErrorCode::AddOperands,
+                message: "Operands must be two numbers or two strings.".to_owned(),
+                line: 1,
+                span: Span::new(0, 7)
+            })
+        );
+        assert_eq!(
+            eval("-true"),
+            Err(RuntimeError {
+                code: ErrorCode::NumberOperands,
+                message: "Operand must be a number.".to_owned(),
+                line: 1,
+                span: Span::new(0, 5)
+            })
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use crate::scanner::TokenType;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+// Optional language additions, each bringing its own keywords
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Extension {
+    // `break` and `continue`
+    LoopControl,
+    // `let` as another way to write `var`
+    Let,
+    // `import`
+    Modules,
+}
+
+impl Extension {
+    pub const ALL: [Extension; 3] = [Extension::LoopControl, Extension::Let, Extension::Modules];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Extension::LoopControl => "loop-control",
+            Extension::Let => "let",
+            Extension::Modules => "modules",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Extension::ALL.into_iter().find(|ext| ext.name() == name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Keyword {
+    pub text: &'static str,
+    pub kind: TokenType,
+    // Keywords without an extension are part of every dialect
+    pub extension: Option<Extension>,
+    // Contextual keywords are scanned as identifiers and only mean something
+    // to the parser in particular positions, so they can still be used as
+    // names everywhere else
+    pub contextual: bool,
+}
+
+const fn keyword(text: &'static str, kind: TokenType) -> Keyword {
+    Keyword {
+        text,
+        kind,
+        extension: None,
+        contextual: false,
+    }
+}
+
+const fn extension(
+    text: &'static str,
+    kind: TokenType,
+    extension: Extension,
+    contextual: bool,
+) -> Keyword {
+    Keyword {
+        text,
+        kind,
+        extension: Some(extension),
+        contextual,
+    }
+}
+
+const KEYWORDS: &[Keyword] = &[
+    keyword("and", TokenType::And),
+    keyword("class", TokenType::Class),
+    keyword("else", TokenType::Else),
+    keyword("false", TokenType::False),
+    keyword("for", TokenType::For),
+    keyword("fun", TokenType::Fun),
+    keyword("if", TokenType::If),
+    keyword("nil", TokenType::Nil),
+    keyword("or", TokenType::Or),
+    keyword("print", TokenType::Print),
+    keyword("return", TokenType::Return),
+    keyword("super", TokenType::Super),
+    keyword("this", TokenType::This),
+    keyword("true", TokenType::True),
+    keyword("var", TokenType::Var),
+    keyword("while", TokenType::While),
+    extension("break", TokenType::Break, Extension::LoopControl, false),
+    extension(
+        "continue",
+        TokenType::Continue,
+        Extension::LoopControl,
+        false,
+    ),
+    extension("let", TokenType::Let, Extension::Let, true),
+    extension("import", TokenType::Import, Extension::Modules, true),
+];
+
+static TABLE: LazyLock<HashMap<&'static str, &'static Keyword>> =
+    LazyLock::new(|| KEYWORDS.iter().map(|k| (k.text, k)).collect());
+
+// The set of extensions a program is scanned and parsed with. The default is
+// plain Lox.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Dialect {
+    // One bit per `Extension`
+    extensions: u8,
+}
+
+impl Dialect {
+    pub fn with(mut self, extension: Extension) -> Self {
+        self.extensions |= 1 << extension as u8;
+        self
+    }
+
+    pub fn has(self, extension: Extension) -> bool {
+        self.extensions & (1 << extension as u8) != 0
+    }
+
+    // The keyword spelled `text`, if this dialect has one
+    pub fn keyword(self, text: &str) -> Option<&'static Keyword> {
+        TABLE
+            .get(text)
+            .copied()
+            .filter(|k| k.extension.is_none_or(|ext| self.has(ext)))
+    }
+
+    // The token kind an identifier-like word scans as
+    pub fn classify(self, text: &str) -> TokenType {
+        match self.keyword(text) {
+            Some(k) if !k.contextual => k.kind,
+            _ => TokenType::Identifier,
+        }
+    }
+
+    // Whether `text` is the contextual keyword `kind` in this dialect
+    pub fn is_contextual(self, text: &str, kind: TokenType) -> bool {
+        self.keyword(text)
+            .is_some_and(|k| k.contextual && k.kind == kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dialects() {
+        let lox = Dialect::default();
+        assert_eq!(lox.classify("while"), TokenType::While);
+        assert_eq!(lox.classify("break"), TokenType::Identifier);
+        assert_eq!(lox.classify("While"), TokenType::Identifier);
+
+        let extended = lox.with(Extension::LoopControl).with(Extension::Let);
+        assert!(extended.has(Extension::Let) && !extended.has(Extension::Modules));
+        assert_eq!(extended.classify("break"), TokenType::Break);
+        assert_eq!(extended.classify("continue"), TokenType::Continue);
+        assert_eq!(extended.classify("while"), TokenType::While);
+
+        // Contextual keywords scan as identifiers either way
+        assert_eq!(extended.classify("let"), TokenType::Identifier);
+        assert!(extended.is_contextual("let", TokenType::Let));
+        assert!(!lox.is_contextual("let", TokenType::Let));
+        assert!(!extended.is_contextual("import", TokenType::Import));
+        assert!(!extended.is_contextual("var", TokenType::Var));
+    }
+
+    #[test]
+    fn extension_names() {
+        for ext in Extension::ALL {
+            assert_eq!(Extension::parse(ext.name()), Some(ext));
+        }
+        assert_eq!(Extension::parse("goto"), None);
+    }
+}
@@ -0,0 +1,713 @@
+use crate::chunk::{Chunk, Constant, Function, Op, UpvalueRef};
+use crate::diagnostics::{Diagnostic, ToDiagnostic};
+use crate::scanner::Span;
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// Precompiled scripts. A file is laid out as
+//
+//   magic    "LOXC"
+//   version  u16
+//   checksum u32, FNV-1a of everything after it
+//   name     the source file's name
+//   source   the source text, so runtime errors can point into it
+//   strings  every name and string constant, stored once
+//   script   the top-level function, with nested functions inline
+//
+// Integers are little-endian; strings and lists are prefixed with a u32
+// length.
+
+const MAGIC: &[u8; 4] = b"LOXC";
+// Bump whenever the layout or the meaning of an op changes
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 10;
+
+pub struct Compiled {
+    pub name: String,
+    pub source: String,
+    pub script: Rc<Function>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LoadError {
+    NotCompiled,
+    Version(u16),
+    Checksum,
+    Malformed(&'static str),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::NotCompiled => write!(f, "not a compiled Lox file"),
+            LoadError::Version(version) => write!(
+                f,
+                "compiled for format version {version} but this is version {VERSION}; recompile it"
+            ),
+            LoadError::Checksum => write!(f, "checksum mismatch; the file is corrupted"),
+            LoadError::Malformed(what) => write!(f, "malformed bytecode: {what}"),
+        }
+    }
+}
+
+impl ToDiagnostic for LoadError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::file_error(&self.to_string())
+    }
+}
+
+pub fn write(name: &str, source: &str, script: &Function) -> Vec<u8> {
+    let mut writer = Writer::default();
+    writer.function(script);
+
+    let mut body = Vec::new();
+    put_str(&mut body, name);
+    put_str(&mut body, source);
+    put_u32(&mut body, writer.strings.len() as u32);
+    for s in &writer.strings {
+        put_str(&mut body, s);
+    }
+    body.extend(writer.out);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + body.len());
+    out.extend(MAGIC);
+    out.extend(VERSION.to_le_bytes());
+    out.extend(checksum(&body).to_le_bytes());
+    out.extend(body);
+    out
+}
+
+pub fn read(bytes: &[u8]) -> Result<Compiled, LoadError> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+        return Err(LoadError::NotCompiled);
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != VERSION {
+        return Err(LoadError::Version(version));
+    }
+    let body = &bytes[HEADER_LEN..];
+    if checksum(body).to_le_bytes() != bytes[6..HEADER_LEN] {
+        return Err(LoadError::Checksum);
+    }
+
+    let mut reader = Reader {
+        bytes: body,
+        pos: 0,
+        source: "",
+        strings: Vec::new(),
+    };
+    let name = reader.str()?.to_owned();
+    reader.source = reader.str()?;
+    for _ in 0..reader.u32()? {
+        let s = Symbol::intern(reader.str()?);
+        reader.strings.push(s);
+    }
+    let script = reader.function()?;
+    if reader.pos != body.len() {
+        return Err(LoadError::Malformed("trailing bytes"));
+    }
+    if !script.upvalues.is_empty() {
+        return Err(LoadError::Malformed("script captures variables"));
+    }
+    if script.arity != 0 {
+        return Err(LoadError::Malformed("script takes arguments"));
+    }
+    Ok(Compiled {
+        name,
+        source: reader.source.to_owned(),
+        script,
+    })
+}
+
+// 32-bit FNV-1a
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, &b| {
+        (hash ^ b as u32).wrapping_mul(0x01000193)
+    })
+}
+
+fn put_u32(out: &mut Vec<u8>, n: u32) {
+    out.extend(n.to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_u32(out, s.len() as u32);
+    out.extend(s.as_bytes());
+}
+
+#[derive(Default)]
+struct Writer {
+    out: Vec<u8>,
+    strings: Vec<Rc<str>>,
+    string_indexes: HashMap<Rc<str>, u32>,
+}
+
+impl Writer {
+    fn u8(&mut self, n: u8) {
+        self.out.push(n);
+    }
+
+    fn u32(&mut self, n: u32) {
+        put_u32(&mut self.out, n);
+    }
+
+    // Writes the string's index in the pool, adding it if it's new
+    fn string(&mut self, s: &str) {
+        let index = match self.string_indexes.get(s) {
+            Some(&index) => index,
+            None => {
+                let s: Rc<str> = Rc::from(s);
+                let index = self.strings.len() as u32;
+                self.strings.push(s.clone());
+                self.string_indexes.insert(s, index);
+                index
+            }
+        };
+        self.u32(index);
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.string(&function.name);
+        self.u32(function.arity as u32);
+        self.u32(function.upvalues.len() as u32);
+        for upvalue in &function.upvalues {
+            self.u8(upvalue.is_local as u8);
+            self.u32(upvalue.index);
+        }
+        self.chunk(&function.chunk);
+    }
+
+    fn chunk(&mut self, chunk: &Chunk) {
+        self.u32(chunk.code.len() as u32);
+        for &op in &chunk.code {
+            self.op(op);
+        }
+        for &line in &chunk.lines {
+            self.out.extend(line.to_le_bytes());
+        }
+        for span in &chunk.spans {
+            self.u32(span.start as u32);
+            self.u32(span.end as u32);
+        }
+        self.u32(chunk.constants.len() as u32);
+        for constant in &chunk.constants {
+            match constant {
+                Constant::Num(n) => {
+                    self.u8(0);
+                    self.out.extend(n.to_le_bytes());
+                }
+                Constant::Str(s) => {
+                    self.u8(1);
+                    self.string(s.as_str());
+                }
+                Constant::Function(function) => {
+                    self.u8(2);
+                    self.function(function);
+                }
+            }
+        }
+    }
+
+    // An opcode byte followed by the op's operands
+    fn op(&mut self, op: Op) {
+        match op {
+            Op::Constant(i) => self.op_with(0, i),
+            Op::Nil => self.u8(1),
+            Op::True => self.u8(2),
+            Op::False => self.u8(3),
+            Op::Pop => self.u8(4),
+            Op::GetLocal(i) => self.op_with(5, i),
+            Op::SetLocal(i) => self.op_with(6, i),
+            Op::GetGlobal(i) => self.op_with(7, i),
+            Op::DefineGlobal(i) => self.op_with(8, i),
+            Op::SetGlobal(i) => self.op_with(9, i),
+            Op::GetUpvalue(i) => self.op_with(10, i),
+            Op::SetUpvalue(i) => self.op_with(11, i),
+            Op::GetProperty(i) => self.op_with(12, i),
+            Op::SetProperty(i) => self.op_with(13, i),
+            Op::GetSuper(i) => self.op_with(14, i),
+            Op::Equal => self.u8(15),
+            Op::NotEqual => self.u8(16),
+            Op::Greater => self.u8(17),
+            Op::GreaterEqual => self.u8(18),
+            Op::Less => self.u8(19),
+            Op::LessEqual => self.u8(20),
+            Op::Add => self.u8(21),
+            Op::Subtract => self.u8(22),
+            Op::Multiply => self.u8(23),
+            Op::Divide => self.u8(24),
+            Op::Not => self.u8(25),
+            Op::Negate => self.u8(26),
+            Op::Interpolate(n) => self.op_with(27, n),
+            Op::Print => self.u8(28),
+            Op::Jump(target) => self.op_with(29, target),
+            Op::JumpIfFalse(target) => self.op_with(30, target),
+            Op::Call(argc) => {
+                self.u8(31);
+                self.u8(argc);
+            }
+            Op::Invoke(i, argc) => {
+                self.op_with(32, i);
+                self.u8(argc);
+            }
+            Op::SuperInvoke(i, argc) => {
+                self.op_with(33, i);
+                self.u8(argc);
+            }
+            Op::Closure(i) => self.op_with(34, i),
+            Op::CloseUpvalue => self.u8(35),
+            Op::Return => self.u8(36),
+            Op::Class(i) => self.op_with(37, i),
+            Op::Inherit => self.u8(38),
+            Op::Method(i) => self.op_with(39, i),
+        }
+    }
+
+    fn op_with(&mut self, opcode: u8, operand: u32) {
+        self.u8(opcode);
+        self.u32(operand);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    // What spans point into
+    source: &'a str,
+    strings: Vec<Symbol>,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], LoadError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or(LoadError::Malformed("unexpected end of file"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], LoadError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, LoadError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn str(&mut self) -> Result<&'a str, LoadError> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.take(len)?).map_err(|_| LoadError::Malformed("invalid UTF-8"))
+    }
+
+    // A string from the pool
+    fn string(&mut self) -> Result<Symbol, LoadError> {
+        let index = self.u32()? as usize;
+        self.strings
+            .get(index)
+            .copied()
+            .ok_or(LoadError::Malformed("string index out of range"))
+    }
+
+    fn function(&mut self) -> Result<Rc<Function>, LoadError> {
+        let name = self.string()?.as_str().to_owned();
+        let arity = self.u32()? as usize;
+        let mut upvalues = Vec::new();
+        for _ in 0..self.u32()? {
+            upvalues.push(UpvalueRef {
+                is_local: self.u8()? != 0,
+                index: self.u32()?,
+            });
+        }
+        let chunk = self.chunk()?;
+        let function = Function {
+            name,
+            arity,
+            upvalues,
+            chunk,
+        };
+        verify(&function)?;
+        Ok(Rc::new(function))
+    }
+
+    fn chunk(&mut self) -> Result<Chunk, LoadError> {
+        let mut chunk = Chunk::default();
+        let len = self.u32()?;
+        for _ in 0..len {
+            let op = self.op()?;
+            chunk.code.push(op);
+        }
+        for _ in 0..len {
+            let line = self.array().map(i32::from_le_bytes)?;
+            chunk.lines.push(line);
+        }
+        for _ in 0..len {
+            let span = Span::new(self.u32()? as usize, self.u32()? as usize);
+            let in_source = span.start <= span.end
+                && self.source.is_char_boundary(span.start)
+                && self.source.is_char_boundary(span.end);
+            if !in_source {
+                return Err(LoadError::Malformed("span out of range"));
+            }
+            chunk.spans.push(span);
+        }
+        for _ in 0..self.u32()? {
+            let constant = match self.u8()? {
+                0 => Constant::Num(self.array().map(f64::from_le_bytes)?),
+                1 => Constant::Str(self.string()?),
+                2 => Constant::Function(self.function()?),
+                _ => return Err(LoadError::Malformed("unknown constant kind")),
+            };
+            chunk.constants.push(constant);
+        }
+        Ok(chunk)
+    }
+
+    fn op(&mut self) -> Result<Op, LoadError> {
+        Ok(match self.u8()? {
+            0 => Op::Constant(self.u32()?),
+            1 => Op::Nil,
+            2 => Op::True,
+            3 => Op::False,
+            4 => Op::Pop,
+            5 => Op::GetLocal(self.u32()?),
+            6 => Op::SetLocal(self.u32()?),
+            7 => Op::GetGlobal(self.u32()?),
+            8 => Op::DefineGlobal(self.u32()?),
+            9 => Op::SetGlobal(self.u32()?),
+            10 => Op::GetUpvalue(self.u32()?),
+            11 => Op::SetUpvalue(self.u32()?),
+            12 => Op::GetProperty(self.u32()?),
+            13 => Op::SetProperty(self.u32()?),
+            14 => Op::GetSuper(self.u32()?),
+            15 => Op::Equal,
+            16 => Op::NotEqual,
+            17 => Op::Greater,
+            18 => Op::GreaterEqual,
+            19 => Op::Less,
+            20 => Op::LessEqual,
+            21 => Op::Add,
+            22 => Op::Subtract,
+            23 => Op::Multiply,
+            24 => Op::Divide,
+            25 => Op::Not,
+            26 => Op::Negate,
+            27 => Op::Interpolate(self.u32()?),
+            28 => Op::Print,
+            29 => Op::Jump(self.u32()?),
+            30 => Op::JumpIfFalse(self.u32()?),
+            31 => Op::Call(self.u8()?),
+            32 => Op::Invoke(self.u32()?, self.u8()?),
+            33 => Op::SuperInvoke(self.u32()?, self.u8()?),
+            34 => Op::Closure(self.u32()?),
+            35 => Op::CloseUpvalue,
+            36 => Op::Return,
+            37 => Op::Class(self.u32()?),
+            38 => Op::Inherit,
+            39 => Op::Method(self.u32()?),
+            _ => return Err(LoadError::Malformed("unknown opcode")),
+        })
+    }
+}
+
+// Checks that running the function can't index past its constant pool, its
+// closure's upvalues, its code or its stack frame. Nested functions are
+// checked as they are read, before the function that creates them.
+fn verify(function: &Function) -> Result<(), LoadError> {
+    let chunk = &function.chunk;
+    for &op in &chunk.code {
+        verify_operands(function, op)?;
+    }
+
+    // Walk every path through the code, tracking how many values sit in the
+    // frame. Like the compiler's output, each op must see the same height on
+    // every path that reaches it.
+    let mut heights = vec![None; chunk.code.len()];
+    // The callee, then the arguments
+    let mut pending = vec![(0, function.arity + 1)];
+    while let Some((offset, height)) = pending.pop() {
+        let Some(&op) = chunk.code.get(offset) else {
+            return Err(LoadError::Malformed("code runs past the end"));
+        };
+        match heights[offset] {
+            Some(seen) if seen == height => continue,
+            Some(_) => return Err(LoadError::Malformed("inconsistent stack height")),
+            None => heights[offset] = Some(height),
+        }
+        let local_out_of_range = match op {
+            Op::GetLocal(slot) | Op::SetLocal(slot) => slot as usize >= height,
+            Op::Closure(index) => {
+                let Constant::Function(nested) = &chunk.constants[index as usize] else {
+                    unreachable!("operands are verified first");
+                };
+                nested
+                    .upvalues
+                    .iter()
+                    .any(|upvalue| upvalue.is_local && upvalue.index as usize >= height)
+            }
+            _ => false,
+        };
+        if local_out_of_range {
+            return Err(LoadError::Malformed("local slot out of range"));
+        }
+        let (pops, pushes) = stack_effect(op);
+        if pops > height {
+            return Err(LoadError::Malformed("stack underflow"));
+        }
+        let height = height - pops + pushes;
+        match op {
+            Op::Return => {}
+            Op::Jump(target) => pending.push((target as usize, height)),
+            Op::JumpIfFalse(target) => {
+                pending.push((target as usize, height));
+                pending.push((offset + 1, height));
+            }
+            _ => pending.push((offset + 1, height)),
+        }
+    }
+    Ok(())
+}
+
+fn verify_operands(function: &Function, op: Op) -> Result<(), LoadError> {
+    let chunk = &function.chunk;
+    let constant = |index: u32| {
+        chunk
+            .constants
+            .get(index as usize)
+            .ok_or(LoadError::Malformed("constant index out of range"))
+    };
+    let wrong_kind = LoadError::Malformed("wrong kind of constant");
+    match op {
+        Op::Constant(index) => {
+            if let Constant::Function(_) = constant(index)? {
+                return Err(wrong_kind);
+            }
+        }
+        Op::GetGlobal(index)
+        | Op::DefineGlobal(index)
+        | Op::SetGlobal(index)
+        | Op::GetProperty(index)
+        | Op::SetProperty(index)
+        | Op::GetSuper(index)
+        | Op::Invoke(index, _)
+        | Op::SuperInvoke(index, _)
+        | Op::Class(index)
+        | Op::Method(index) => {
+            let Constant::Str(_) = constant(index)? else {
+                return Err(wrong_kind);
+            };
+        }
+        Op::Closure(index) => {
+            let Constant::Function(nested) = constant(index)? else {
+                return Err(wrong_kind);
+            };
+            let out_of_range = |upvalue: &UpvalueRef| {
+                !upvalue.is_local && upvalue.index as usize >= function.upvalues.len()
+            };
+            if nested.upvalues.iter().any(out_of_range) {
+                return Err(LoadError::Malformed("upvalue index out of range"));
+            }
+        }
+        Op::GetUpvalue(index) | Op::SetUpvalue(index)
+            if index as usize >= function.upvalues.len() =>
+        {
+            return Err(LoadError::Malformed("upvalue index out of range"));
+        }
+        Op::Jump(target) | Op::JumpIfFalse(target) if target as usize >= chunk.code.len() => {
+            return Err(LoadError::Malformed("jump target out of range"));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// How many values an op pops off the stack and pushes back on
+fn stack_effect(op: Op) -> (usize, usize) {
+    match op {
+        Op::Constant(_)
+        | Op::Nil
+        | Op::True
+        | Op::False
+        | Op::GetLocal(_)
+        | Op::GetGlobal(_)
+        | Op::GetUpvalue(_)
+        | Op::Closure(_)
+        | Op::Class(_) => (0, 1),
+        Op::Pop | Op::DefineGlobal(_) | Op::Print | Op::CloseUpvalue | Op::Return => (1, 0),
+        Op::SetLocal(_)
+        | Op::SetGlobal(_)
+        | Op::SetUpvalue(_)
+        | Op::GetProperty(_)
+        | Op::Not
+        | Op::Negate
+        | Op::JumpIfFalse(_) => (1, 1),
+        Op::SetProperty(_)
+        | Op::GetSuper(_)
+        | Op::Equal
+        | Op::NotEqual
+        | Op::Greater
+        | Op::GreaterEqual
+        | Op::Less
+        | Op::LessEqual
+        | Op::Add
+        | Op::Subtract
+        | Op::Multiply
+        | Op::Divide
+        | Op::Inherit
+        | Op::Method(_) => (2, 1),
+        Op::Interpolate(count) => (count as usize, 1),
+        Op::Jump(_) => (0, 0),
+        // The callee or receiver, then the arguments
+        Op::Call(argc) | Op::Invoke(_, argc) => (argc as usize + 1, 1),
+        // Plus the superclass
+        Op::SuperInvoke(_, argc) => (argc as usize + 2, 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler;
+    use crate::diagnostics::SourceFile;
+    use crate::error_code::ErrorCode;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::vm::Vm;
+
+    const SOURCE: &str = "
+        class A { init(x) { this.x = x; } get() { return this.x; } }
+        class B < A { get() { return -super.get() * 1.5; } }
+        fun counter() {
+            var n = 0;
+            fun inc() { n = n + 1; return \"${n}\"; }
+            return inc;
+        }
+        var b = B(2);
+        print b.get() != nil and !false or true;
+        for (var i = 0; i <= 2; i = i + 1) { print counter()(); }
+    ";
+
+    fn compile(source: &str) -> Rc<Function> {
+        let file = SourceFile::new("test.lox", source);
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        compiler::compile(&file, &program)
+    }
+
+    #[test]
+    fn round_trip() {
+        let script = compile(SOURCE);
+        let bytes = write("test.lox", SOURCE, &script);
+        let compiled = read(&bytes).unwrap();
+        assert_eq!(compiled.name, "test.lox");
+        assert_eq!(compiled.source, SOURCE);
+        assert_eq!(compiled.script, script);
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let bytes = write("test.lox", SOURCE, &compile(SOURCE));
+        assert_eq!(read(b"var a = 1;").err(), Some(LoadError::NotCompiled));
+
+        let mut stale = bytes.clone();
+        stale[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(read(&stale).err(), Some(LoadError::Version(VERSION + 1)));
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(read(&corrupted).err(), Some(LoadError::Checksum));
+
+        // A truncated file fails the checksum before it's decoded
+        assert_eq!(
+            read(&bytes[..bytes.len() - 1]).err(),
+            Some(LoadError::Checksum)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_operands() {
+        let load = |code: Vec<Op>| {
+            let mut script = Function::default();
+            script.chunk.constants.push(Constant::Num(1.0));
+            for op in code {
+                script.chunk.write(op, 1, Span::new(0, 0));
+            }
+            read(&write("test.lox", "", &script)).err()
+        };
+        let malformed = |what| Some(LoadError::Malformed(what));
+        assert_eq!(load(vec![Op::Nil, Op::Return]), None);
+        assert_eq!(
+            load(vec![Op::Constant(1), Op::Return]),
+            malformed("constant index out of range")
+        );
+        assert_eq!(
+            load(vec![Op::GetGlobal(0), Op::Return]),
+            malformed("wrong kind of constant")
+        );
+        assert_eq!(
+            load(vec![Op::GetUpvalue(0), Op::Return]),
+            malformed("upvalue index out of range")
+        );
+        assert_eq!(
+            load(vec![Op::Jump(3), Op::Nil, Op::Return]),
+            malformed("jump target out of range")
+        );
+        assert_eq!(
+            load(vec![Op::GetLocal(1), Op::Return]),
+            malformed("local slot out of range")
+        );
+        assert_eq!(
+            load(vec![Op::Pop, Op::Return]),
+            malformed("stack underflow")
+        );
+        assert_eq!(load(vec![Op::Nil]), malformed("code runs past the end"));
+        assert_eq!(
+            load(vec![Op::True, Op::JumpIfFalse(3), Op::Nil, Op::Return]),
+            malformed("inconsistent stack height")
+        );
+    }
+
+    #[test]
+    fn rejects_bad_scripts() {
+        let mut script = Function::default();
+        script.chunk.write(Op::Nil, 1, Span::new(0, 1));
+        script.chunk.write(Op::Return, 1, Span::new(0, 1));
+        let malformed = |what| Some(LoadError::Malformed(what));
+        assert!(read(&write("test.lox", "nil", &script)).is_ok());
+        // Spans must point into the source, at character boundaries
+        assert_eq!(
+            read(&write("test.lox", "", &script)).err(),
+            malformed("span out of range")
+        );
+        assert_eq!(
+            read(&write("test.lox", "é", &script)).err(),
+            malformed("span out of range")
+        );
+        script.arity = 1;
+        assert_eq!(
+            read(&write("test.lox", "nil", &script)).err(),
+            malformed("script takes arguments")
+        );
+    }
+
+    #[test]
+    fn runs_ill_typed_code_without_panicking() {
+        let mut script = Function::default();
+        let name = script
+            .chunk
+            .add_constant(Constant::Str(Symbol::intern("m")));
+        for op in [Op::Nil, Op::Nil, Op::Method(name), Op::Return] {
+            script.chunk.write(op, 1, Span::new(0, 0));
+        }
+        let compiled = read(&write("test.lox", "", &script)).unwrap();
+        let e = Vm::new().interpret(compiled.script).unwrap_err();
+        assert_eq!(e.code, ErrorCode::InvalidBytecode);
+        assert_eq!(e.message, "Invalid bytecode: method is not a function.");
+    }
+}
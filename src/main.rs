@@ -1,25 +1,259 @@
+mod chunk;
+mod class;
+mod compiler;
+mod diagnostics;
+mod disassembler;
+mod environment;
+mod error_code;
+mod function;
+mod gc;
+mod interpreter;
+mod keywords;
+mod loxc;
+mod object;
 mod parser;
+mod resolver;
 mod scanner;
+mod symbol;
+mod vm;
 
-use parser::Parser;
-use scanner::Scanner;
+use diagnostics::{Diagnostic, ErrorFormat, SourceFile, ToDiagnostic};
+use error_code::ErrorCode;
+use interpreter::Interpreter;
+use keywords::{Dialect, Extension};
+use parser::{Parser, Stmt};
+use scanner::{Scanner, TokenType, Trivia};
 use std::io::Write;
-use std::{env, fs, io};
+use std::path::Path;
+use std::{env, fs, io, process, thread};
+use typed_arena::Arena;
+use vm::Vm;
+
+#[derive(Clone, Copy)]
+enum Backend {
+    TreeWalker,
+    Vm,
+}
+
+impl Backend {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "tree" => Some(Backend::TreeWalker),
+            "vm" => Some(Backend::Vm),
+            _ => None,
+        }
+    }
+}
+
+// Whichever backend runs the program; state such as globals lives here and
+// carries over between REPL lines
+enum Runtime<'src> {
+    TreeWalker(Interpreter<'src>),
+    Vm(Box<Vm>),
+}
+
+impl Runtime<'_> {
+    fn new(backend: Backend, trace: bool, gc_stress: bool) -> Self {
+        match backend {
+            Backend::TreeWalker => Runtime::TreeWalker(Interpreter::new()),
+            Backend::Vm => {
+                let vm = Vm::new().with_trace(trace).with_gc_stress(gc_stress);
+                Runtime::Vm(Box::new(vm))
+            }
+        }
+    }
+}
+
+// Why a program didn't run to completion
+#[derive(Clone, Copy)]
+enum Failure {
+    Static,
+    Runtime,
+    Io,
+}
+
+impl Failure {
+    // Following the sysexits convention
+    fn exit_code(self) -> i32 {
+        match self {
+            Failure::Static => 65,
+            Failure::Runtime => 70,
+            Failure::Io => 74,
+        }
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    // The tree-walker recurses on the native stack, so run on a thread with
+    // room for its deepest calls
+    let cli = thread::Builder::new()
+        .stack_size(interpreter::STACK_SIZE)
+        .spawn(cli)
+        .expect("Failed to start interpreter thread");
+    if cli.join().is_err() {
+        process::exit(101);
+    }
+}
 
-    if args.len() > 2 {
-        eprintln!("Usage: zero or one argument needed (filename)");
-    } else if args.len() == 2 {
-        let source_code = fs::read_to_string(&args[1]).expect("Failed to read file");
-        run(&source_code);
-    } else {
-        run_prompt();
+fn cli() {
+    let mut format = ErrorFormat::Human;
+    let mut dump_tokens = false;
+    let mut backend = Backend::TreeWalker;
+    let mut disassemble = false;
+    let mut trace = false;
+    let mut gc_stress = false;
+    let mut dialect = Dialect::default();
+    let mut output = None;
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--explain" {
+            return match args.next() {
+                Some(code) => explain(&code),
+                None => eprintln!("Usage: rox --explain <code>"),
+            };
+        } else if arg == "-o" {
+            match args.next() {
+                Some(path) => output = Some(path),
+                None => return eprintln!("Expected an output path after -o"),
+            }
+        } else if arg == "--tokens" {
+            dump_tokens = true;
+        } else if arg == "--disassemble" {
+            disassemble = true;
+        } else if arg == "--trace" {
+            trace = true;
+        } else if arg == "--gc-stress" {
+            gc_stress = true;
+        } else if let Some(names) = arg.strip_prefix("--enable=") {
+            for name in names.split(',') {
+                match Extension::parse(name) {
+                    Some(extension) => dialect = dialect.with(extension),
+                    None => {
+                        let known = Extension::ALL.map(Extension::name).join(", ");
+                        return eprintln!("Unknown extension '{name}' (expected one of {known})");
+                    }
+                }
+            }
+        } else if let Some(name) = arg.strip_prefix("--backend=") {
+            match Backend::parse(name) {
+                Some(b) => backend = b,
+                None => return eprintln!("Unknown backend '{name}' (expected tree or vm)"),
+            }
+        } else if let Some(name) = arg.strip_prefix("--error-format=") {
+            match ErrorFormat::parse(name) {
+                Some(f) => format = f,
+                None => return eprintln!("Unknown error format '{name}' (expected human or json)"),
+            }
+        } else {
+            paths.push(arg);
+        }
+    }
+
+    // Precompiled files always run on the VM
+    let precompiled = paths.first().is_some_and(|command| command == "run");
+    if (trace || gc_stress) && !precompiled && !matches!(backend, Backend::Vm) {
+        return eprintln!("--trace and --gc-stress require --backend=vm");
+    }
+
+    let result = match paths.as_slice() {
+        [command, path] if command == "compile" => {
+            compile_file(path, output.as_deref(), format, dialect)
+        }
+        [command, path] if command == "run" => run_compiled(path, format, trace, gc_stress),
+        [_, _, ..] => {
+            eprintln!(
+                "Usage: rox [--error-format=human|json] [--enable=<extension>,...] [--backend=tree|vm]"
+            );
+            eprintln!("           [--trace] [--gc-stress] [--tokens] [--disassemble] [filename]");
+            eprintln!("       rox compile <filename> [-o <output>]");
+            eprintln!("       rox run <filename.loxc>");
+            eprintln!("       rox --explain <code>");
+            Ok(())
+        }
+        [path] => match fs::read_to_string(path) {
+            Ok(source_code) => {
+                let file = SourceFile::new(path, &source_code);
+                if dump_tokens {
+                    print_tokens(&file, format, dialect)
+                } else if disassemble {
+                    print_bytecode(&file, format, dialect)
+                } else {
+                    run(
+                        &file,
+                        &mut Runtime::new(backend, trace, gc_stress),
+                        format,
+                        dialect,
+                        false,
+                    )
+                }
+            }
+            Err(e) => Err(file_error(
+                path,
+                &format!("couldn't read file: {e}"),
+                format,
+            )),
+        },
+        [] => {
+            // Functions and classes defined at the prompt borrow from the
+            // line they were typed on, so lines are kept until the runtime
+            // is gone
+            let lines = Arena::new();
+            run_prompt(
+                &lines,
+                format,
+                dialect,
+                Runtime::new(backend, trace, gc_stress),
+            );
+            Ok(())
+        }
+    };
+    if let Err(failure) = result {
+        process::exit(failure.exit_code());
+    }
+}
+
+fn explain(code: &str) {
+    match ErrorCode::parse(code) {
+        Some(code) => print!("{}", code.explanation()),
+        None => eprintln!("'{code}' is not a valid error code"),
+    }
+}
+
+// Lists every token along with the trivia around it, one per line
+fn print_tokens(file: &SourceFile, format: ErrorFormat, dialect: Dialect) -> Result<(), Failure> {
+    let (tokens, errors) = Scanner::new(file.source)
+        .with_dialect(dialect)
+        .scan_lossless();
+    let print_trivia = |trivia: &[Trivia]| {
+        for t in trivia {
+            println!(
+                "    {:?} {}..{} {:?}",
+                t.kind, t.span.start, t.span.end, t.text
+            );
+        }
+    };
+    for t in &tokens {
+        print_trivia(&t.leading);
+        let token = &t.token;
+        println!(
+            "{}:{} {:?} {}..{} {:?}",
+            token.line, token.column, token.kind, token.span.start, token.span.end, token.lexeme
+        );
+        print_trivia(&t.trailing);
+    }
+    if !errors.is_empty() {
+        return Err(report(file, format, &errors));
     }
+    Ok(())
 }
 
-fn run_prompt() {
+fn run_prompt<'a>(
+    lines: &'a Arena<String>,
+    format: ErrorFormat,
+    dialect: Dialect,
+    mut runtime: Runtime<'a>,
+) {
     loop {
         print!("> ");
         io::stdout().flush().expect("Failed to flush output");
@@ -29,16 +263,171 @@ fn run_prompt() {
         if line.is_empty() {
             break;
         }
-        run(&line);
+        let line = lines.alloc(line).as_str();
+        // Errors are reported and the session carries on
+        let _ = run(
+            &SourceFile::new("<repl>", line),
+            &mut runtime,
+            format,
+            dialect,
+            true,
+        );
+    }
+}
+
+fn run<'src>(
+    file: &SourceFile<'src>,
+    runtime: &mut Runtime<'src>,
+    format: ErrorFormat,
+    dialect: Dialect,
+    repl: bool,
+) -> Result<(), Failure> {
+    let program = if repl {
+        let tokens = match Scanner::new(file.source)
+            .with_dialect(dialect)
+            .scan_tokens()
+        {
+            Ok(tokens) => tokens,
+            Err(errors) => return Err(report(file, format, &errors)),
+        };
+        // In the REPL, a line without a trailing `;` or `}` is a bare
+        // expression whose value gets printed
+        let bare_expr = tokens.len() > 1
+            && !matches!(
+                tokens[tokens.len() - 2].kind,
+                TokenType::Semicolon | TokenType::RightBrace
+            );
+        if bare_expr {
+            let expr = match Parser::new(tokens).with_dialect(dialect).parse() {
+                Ok(expr) => expr,
+                Err(errors) => return Err(report(file, format, &errors)),
+            };
+            if let Err(errors) = resolver::resolve_expression(&expr) {
+                return Err(report(file, format, &errors));
+            }
+            let result = match runtime {
+                Runtime::TreeWalker(interpreter) => {
+                    interpreter.evaluate(&expr).map(|value| value.to_string())
+                }
+                Runtime::Vm(vm) => vm
+                    .interpret(compiler::compile_expression(file, &expr))
+                    .map(|value| value.display(vm.heap()).to_string()),
+            };
+            return match result {
+                Ok(value) => {
+                    println!("{value}");
+                    Ok(())
+                }
+                Err(e) => {
+                    e.to_diagnostic().emit(file, format);
+                    Err(Failure::Runtime)
+                }
+            };
+        }
+        match Parser::new(tokens).with_dialect(dialect).parse_program() {
+            Ok(program) => program,
+            Err(errors) => return Err(report(file, format, &errors)),
+        }
+    } else {
+        parse_file(file, format, dialect)?
+    };
+    if let Err(errors) = resolver::resolve(&program) {
+        return Err(report(file, format, &errors));
+    }
+    let result = match runtime {
+        Runtime::TreeWalker(interpreter) => interpreter.interpret(&program),
+        Runtime::Vm(vm) => vm.interpret(compiler::compile(file, &program)).map(|_| ()),
+    };
+    result.map_err(|e| {
+        e.to_diagnostic().emit(file, format);
+        Failure::Runtime
+    })
+}
+
+// Parses a whole file straight from the scanner. Scan errors are set aside
+// and reported in place of any parse errors.
+fn parse_file<'src>(
+    file: &SourceFile<'src>,
+    format: ErrorFormat,
+    dialect: Dialect,
+) -> Result<Vec<Stmt<'src>>, Failure> {
+    let mut scan_errors = Vec::new();
+    let tokens = Scanner::new(file.source)
+        .with_dialect(dialect)
+        .filter_map(|token| token.map_err(|e| scan_errors.push(e)).ok());
+    let program = Parser::new(tokens).with_dialect(dialect).parse_program();
+    if !scan_errors.is_empty() {
+        return Err(report(file, format, &scan_errors));
+    }
+    program.map_err(|errors| report(file, format, &errors))
+}
+
+// Prints the bytecode the file compiles to instead of running it
+fn print_bytecode(file: &SourceFile, format: ErrorFormat, dialect: Dialect) -> Result<(), Failure> {
+    let program = parse_file(file, format, dialect)?;
+    if let Err(errors) = resolver::resolve(&program) {
+        return Err(report(file, format, &errors));
     }
+    print!(
+        "{}",
+        disassembler::disassemble(&compiler::compile(file, &program))
+    );
+    Ok(())
 }
 
-fn run(source: &str) {
-    let mut s = Scanner::new(source);
-    if let Ok(tokens) = s.scan_tokens() {
-        println!("Done scanning, number of tokens = {}", tokens.len());
-        let mut p = Parser::new(tokens);
-        let expr = p.parse();
-        println!("expression = {expr:?}");
+// Writes the file's bytecode next to it, or to `output`
+fn compile_file(
+    path: &str,
+    output: Option<&str>,
+    format: ErrorFormat,
+    dialect: Dialect,
+) -> Result<(), Failure> {
+    let source_code = fs::read_to_string(path)
+        .map_err(|e| file_error(path, &format!("couldn't read file: {e}"), format))?;
+    let file = SourceFile::new(path, &source_code);
+    let program = parse_file(&file, format, dialect)?;
+    if let Err(errors) = resolver::resolve(&program) {
+        return Err(report(&file, format, &errors));
     }
+    let script = compiler::compile(&file, &program);
+    let output = match output {
+        Some(output) => Path::new(output).to_owned(),
+        None => Path::new(path).with_extension("loxc"),
+    };
+    let bytes = loxc::write(path, &source_code, &script);
+    fs::write(&output, bytes).map_err(|e| {
+        let output = output.to_string_lossy();
+        file_error(&output, &format!("couldn't write file: {e}"), format)
+    })
+}
+
+fn run_compiled(
+    path: &str,
+    format: ErrorFormat,
+    trace: bool,
+    gc_stress: bool,
+) -> Result<(), Failure> {
+    let bytes = fs::read(path)
+        .map_err(|e| file_error(path, &format!("couldn't read file: {e}"), format))?;
+    let compiled =
+        loxc::read(&bytes).map_err(|e| report(&SourceFile::new(path, ""), format, &[e]))?;
+    let file = SourceFile::new(&compiled.name, &compiled.source);
+    let mut vm = Vm::new().with_trace(trace).with_gc_stress(gc_stress);
+    vm.interpret(compiled.script).map(|_| ()).map_err(|e| {
+        e.to_diagnostic().emit(&file, format);
+        Failure::Runtime
+    })
+}
+
+fn report(file: &SourceFile, format: ErrorFormat, errors: &[impl ToDiagnostic]) -> Failure {
+    for e in errors {
+        e.to_diagnostic().emit(file, format);
+    }
+    Failure::Static
+}
+
+// Reports an error about a file as a whole, such as one that can't be opened
+fn file_error(path: &str, message: &str, format: ErrorFormat) -> Failure {
+    Diagnostic::file_error(message).emit(&SourceFile::new(path, ""), format);
+    Failure::Io
 }
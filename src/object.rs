@@ -0,0 +1,119 @@
+use crate::chunk::Function;
+use crate::gc::{Gc, Heap};
+use crate::symbol::{LoxString, Symbol};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A value on the VM's stack
+#[derive(Clone, Debug)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Num(f64),
+    Str(LoxString),
+    Closure(Gc<Closure>),
+    Native(Rc<Native>),
+    Class(Gc<Class>),
+    Instance(Gc<Instance>),
+    BoundMethod(Gc<BoundMethod>),
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    // Printing objects means looking them up on the heap
+    pub fn display<'a>(&'a self, heap: &'a Heap) -> Display<'a> {
+        Display { value: self, heap }
+    }
+}
+
+pub struct Display<'a> {
+    value: &'a Value,
+    heap: &'a Heap,
+}
+
+impl fmt::Display for Display<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let heap = self.heap;
+        match self.value {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Num(n) => write!(f, "{n}"),
+            Value::Str(s) => write!(f, "{s}"),
+            Value::Closure(c) => write!(f, "{}", heap.get(*c).function),
+            Value::Native(n) => write!(f, "<native fn {}>", n.name),
+            Value::Class(c) => write!(f, "{}", heap.get(*c).name),
+            Value::Instance(i) => write!(f, "{} instance", heap.get(heap.get(*i).class).name),
+            Value::BoundMethod(b) => {
+                write!(f, "{}", heap.get(heap.get(*b).method).function)
+            }
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Num(a), Value::Num(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Closure(a), Value::Closure(b)) => a == b,
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => a == b,
+            (Value::Instance(a), Value::Instance(b)) => a == b,
+            (Value::BoundMethod(a), Value::BoundMethod(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Gc<Upvalue>>,
+}
+
+// A variable captured by a closure. It stays on the stack while the function
+// declaring it is running, and moves into the upvalue once that returns.
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Native {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&[Value]) -> Value,
+}
+
+pub fn natives() -> Vec<Native> {
+    vec![Native {
+        name: "clock",
+        arity: 0,
+        function: |_| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            Value::Num(now.as_secs_f64())
+        },
+    }]
+}
+
+pub struct Class {
+    pub name: Symbol,
+    // Inherited methods are copied in when the class is declared
+    pub methods: HashMap<Symbol, Gc<Closure>>,
+}
+
+pub struct Instance {
+    pub class: Gc<Class>,
+    pub fields: HashMap<Symbol, Value>,
+}
+
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Gc<Closure>,
+}
@@ -1,77 +1,656 @@
-use crate::scanner::{Token, TokenType};
+use crate::diagnostics::{Diagnostic, Label, ToDiagnostic};
+use crate::error_code::ErrorCode;
+use crate::keywords::Dialect;
+use crate::scanner::{Span, Token, TokenType};
+use crate::symbol::Symbol;
+use std::cell::Cell;
+use std::fmt;
+use std::iter::Peekable;
+use std::rc::Rc;
+
+const MAX_ARGS: usize = 255;
 
 #[derive(PartialEq, Debug)]
-enum Expression {
-    Literal(Literal),
+pub enum Expression<'src> {
+    Literal(Literal, Span),
     Unary {
-        op: TokenType,
-        e: Box<Expression>,
+        op: Token<'src>,
+        e: Box<Expression<'src>>,
     },
     Binary {
-        e1: Box<Expression>,
-        op: TokenType,
-        e2: Box<Expression>,
+        e1: Box<Expression<'src>>,
+        op: Token<'src>,
+        e2: Box<Expression<'src>>,
+    },
+    // `depth` is the number of scopes between a local variable's use and its
+    // declaration, filled in by the resolver; None means the variable is global
+    Variable {
+        name: Token<'src>,
+        depth: Cell<Option<usize>>,
+    },
+    Assign {
+        name: Token<'src>,
+        value: Box<Expression<'src>>,
+        depth: Cell<Option<usize>>,
+    },
+    Logical {
+        e1: Box<Expression<'src>>,
+        op: Token<'src>,
+        e2: Box<Expression<'src>>,
+    },
+    Call {
+        callee: Box<Expression<'src>>,
+        paren: Token<'src>,
+        arguments: Vec<Expression<'src>>,
+    },
+    Get {
+        object: Box<Expression<'src>>,
+        name: Token<'src>,
+    },
+    Set {
+        object: Box<Expression<'src>>,
+        name: Token<'src>,
+        value: Box<Expression<'src>>,
+    },
+    This {
+        keyword: Token<'src>,
+        depth: Cell<Option<usize>>,
     },
+    Super {
+        keyword: Token<'src>,
+        method: Token<'src>,
+        depth: Cell<Option<usize>>,
+    },
+    // String fragments alternating with embedded expressions, starting and
+    // ending with a (possibly empty) fragment
+    Interpolation(Vec<Expression<'src>>),
 }
 
-#[derive(PartialEq, Debug)]
-enum Literal {
+impl Expression<'_> {
+    // The source range this expression was parsed from, excluding any
+    // enclosing parentheses
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Literal(_, span) => *span,
+            Expression::Unary { op, e } => op.span.to(e.span()),
+            Expression::Binary { e1, e2, .. } | Expression::Logical { e1, e2, .. } => {
+                e1.span().to(e2.span())
+            }
+            Expression::Variable { name, .. } => name.span,
+            Expression::Assign { name, value, .. } => name.span.to(value.span()),
+            Expression::Call { callee, paren, .. } => callee.span().to(paren.span),
+            Expression::Get { object, name } => object.span().to(name.span),
+            Expression::Set { object, value, .. } => object.span().to(value.span()),
+            Expression::This { keyword, .. } => keyword.span,
+            Expression::Super {
+                keyword, method, ..
+            } => keyword.span.to(method.span),
+            Expression::Interpolation(parts) => parts[0].span().to(parts[parts.len() - 1].span()),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Literal {
     Num(f64),
-    Str(String),
+    Str(Symbol),
     Bool(bool),
     Nil,
 }
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    cur_idx: usize,
+#[derive(PartialEq, Debug)]
+pub enum Stmt<'src> {
+    Expression(Expression<'src>),
+    Print(Expression<'src>),
+    Var {
+        name: Token<'src>,
+        initializer: Option<Expression<'src>>,
+    },
+    Block(Vec<Stmt<'src>>),
+    If {
+        condition: Expression<'src>,
+        then_branch: Box<Stmt<'src>>,
+        else_branch: Option<Box<Stmt<'src>>>,
+    },
+    While {
+        condition: Expression<'src>,
+        body: Box<Stmt<'src>>,
+    },
+    Function(Rc<FunctionDecl<'src>>),
+    Return {
+        keyword: Token<'src>,
+        value: Option<Expression<'src>>,
+    },
+    Class {
+        name: Token<'src>,
+        superclass: Option<Expression<'src>>,
+        methods: Vec<Rc<FunctionDecl<'src>>>,
+    },
+}
+
+#[derive(PartialEq, Debug)]
+pub struct FunctionDecl<'src> {
+    pub name: Token<'src>,
+    pub params: Vec<Token<'src>>,
+    pub body: Vec<Stmt<'src>>,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, cur_idx: 0 }
+#[derive(PartialEq, Debug)]
+pub struct ParseError<'src> {
+    pub token: Token<'src>,
+    pub code: ErrorCode,
+    pub message: String,
+    // Whether the token is something other than what the grammar expected,
+    // so the diagnostic should say what was found instead
+    pub found: bool,
+    // Another location that helps explain the error, boxed to keep
+    // `Result<_, ParseError<'src>>` small
+    pub related: Option<Box<Label>>,
+}
+
+impl ParseError<'_> {
+    fn with_related(mut self, span: Span, message: &str) -> Self {
+        self.related = Some(Box::new(Label::new(span, message)));
+        self
+    }
+}
+
+impl ToDiagnostic for ParseError<'_> {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(&self.message, self.token.span).with_code(self.code);
+        if self.found {
+            diagnostic = if self.token.kind == TokenType::Eof {
+                diagnostic.with_label("found end of file")
+            } else {
+                diagnostic.with_label(&format!("found '{}'", self.token.lexeme))
+            };
+        }
+        if let Some(related) = &self.related {
+            diagnostic = diagnostic.with_secondary(related.span, &related.message);
+        }
+        diagnostic
+    }
+}
+
+impl fmt::Display for ParseError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.token.kind == TokenType::Eof {
+            write!(
+                f,
+                "[line {}] Error at end: {}",
+                self.token.line, self.message
+            )
+        } else {
+            write!(
+                f,
+                "[line {}] Error at '{}': {}",
+                self.token.line, self.token.lexeme, self.message
+            )
+        }
+    }
+}
+
+// Pulls tokens from `tokens` as it goes, so it can parse straight from a
+// `Scanner`
+pub struct Parser<'src, I: Iterator<Item = Token<'src>>> {
+    tokens: Peekable<I>,
+    current: Option<Token<'src>>,
+    previous: Option<Token<'src>>,
+    errors: Vec<ParseError<'src>>,
+    dialect: Dialect,
+}
+
+impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
+    pub fn new(tokens: impl IntoIterator<IntoIter = I>) -> Self {
+        let mut tokens = tokens.into_iter().peekable();
+        Parser {
+            current: tokens.next(),
+            tokens,
+            previous: None,
+            errors: Vec::new(),
+            dialect: Dialect::default(),
+        }
+    }
+
+    // Should match the dialect the tokens were scanned with
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    pub fn parse(&mut self) -> Result<Expression<'src>, Vec<ParseError<'src>>> {
+        match self.expression() {
+            Ok(expr) if self.errors.is_empty() => Ok(expr),
+            Ok(_) => Err(std::mem::take(&mut self.errors)),
+            Err(e) => {
+                self.errors.push(e);
+                Err(std::mem::take(&mut self.errors))
+            }
+        }
+    }
+
+    pub fn parse_program(&mut self) -> Result<Vec<Stmt<'src>>, Vec<ParseError<'src>>> {
+        let mut statements = Vec::new();
+        while !self.is_at_end() {
+            statements.extend(self.declaration());
+        }
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.current().is_none_or(|cur| cur.kind == TokenType::Eof)
     }
 
-    pub fn parse(&mut self) -> Expression {
-        self.expression()
+    fn current(&self) -> Option<&Token<'src>> {
+        self.current.as_ref()
     }
 
-    fn current(&self) -> Option<&Token> {
-        self.tokens.get(self.cur_idx)
+    fn check(&self, kind: TokenType) -> bool {
+        self.current().is_some_and(|cur| cur.kind == kind)
     }
 
     fn advance(&mut self) {
-        self.cur_idx += 1;
+        let next = self.tokens.next();
+        self.previous = std::mem::replace(&mut self.current, next);
     }
 
-    fn advance_if_eq(&mut self, tokens: &Vec<TokenType>) -> Option<TokenType> {
-        if let Some(cur) = self.current() {
-            for token in tokens {
-                if cur.kind == *token {
-                    self.advance();
-                    return Some(*token);
+    fn advance_if_eq(&mut self, tokens: &[TokenType]) -> Option<Token<'src>> {
+        if !tokens.contains(&self.current()?.kind) {
+            return None;
+        }
+        self.advance();
+        self.previous.clone()
+    }
+
+    fn consume(&mut self, kind: TokenType, message: &str) -> Result<Token<'src>, ParseError<'src>> {
+        self.advance_if_eq(&[kind])
+            .ok_or_else(|| self.expected(missing_token_code(kind), message))
+    }
+
+    // An error for a current token that doesn't fit the grammar here
+    fn expected(&self, code: ErrorCode, message: &str) -> ParseError<'src> {
+        ParseError {
+            found: true,
+            ..self.error_at_current(code, message)
+        }
+    }
+
+    fn error_at_current(&self, code: ErrorCode, message: &str) -> ParseError<'src> {
+        let token = match self.current() {
+            Some(cur) => cur.clone(),
+            // Hand-built token streams may lack a trailing Eof
+            None => match &self.previous {
+                Some(last) => Token {
+                    kind: TokenType::Eof,
+                    lexeme: "",
+                    line: last.line,
+                    column: last.column + last.lexeme.chars().count(),
+                    span: Span::new(last.span.end, last.span.end),
+                    literal: None,
+                    symbol: None,
+                },
+                None => Token {
+                    kind: TokenType::Eof,
+                    lexeme: "",
+                    line: 1,
+                    column: 1,
+                    span: Span::default(),
+                    literal: None,
+                    symbol: None,
+                },
+            },
+        };
+        ParseError {
+            token,
+            code,
+            message: message.to_owned(),
+            found: false,
+            related: None,
+        }
+    }
+
+    // Discards tokens until the start of the next statement, so that a single
+    // syntax error doesn't cascade into many
+    fn synchronize(&mut self) {
+        self.advance();
+        while !self.is_at_end() {
+            if self
+                .previous
+                .as_ref()
+                .is_some_and(|previous| previous.kind == TokenType::Semicolon)
+            {
+                return;
+            }
+            if matches!(
+                self.current().unwrap().kind,
+                TokenType::Class
+                    | TokenType::Fun
+                    | TokenType::Var
+                    | TokenType::For
+                    | TokenType::If
+                    | TokenType::While
+                    | TokenType::Print
+                    | TokenType::Return
+            ) {
+                return;
+            }
+            self.advance();
+        }
+    }
+
+    fn declaration(&mut self) -> Option<Stmt<'src>> {
+        let result = if self.variable_keyword() {
+            self.var_declaration()
+        } else if self.advance_if_eq(&[TokenType::Fun]).is_some() {
+            self.function("function")
+                .map(|function| Stmt::Function(Rc::new(function)))
+        } else if self.advance_if_eq(&[TokenType::Class]).is_some() {
+            self.class_declaration()
+        } else {
+            self.statement()
+        };
+        match result {
+            Ok(stmt) => Some(stmt),
+            Err(e) => {
+                self.errors.push(e);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        let name = self.consume(TokenType::Identifier, "expected class name")?;
+        let superclass = match self.advance_if_eq(&[TokenType::Less]) {
+            Some(_) => Some(Expression::Variable {
+                name: self.consume(TokenType::Identifier, "expected superclass name")?,
+                depth: Cell::new(None),
+            }),
+            None => None,
+        };
+        self.consume(TokenType::LeftBrace, "expected '{' before class body")?;
+        let mut methods = Vec::new();
+        while !self.is_at_end() && !self.check(TokenType::RightBrace) {
+            methods.push(Rc::new(self.function("method")?));
+        }
+        self.consume(TokenType::RightBrace, "expected '}' after class body")?;
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+        })
+    }
+
+    fn function(&mut self, kind: &str) -> Result<FunctionDecl<'src>, ParseError<'src>> {
+        let name = self.consume(TokenType::Identifier, &format!("expected {kind} name"))?;
+        self.consume(
+            TokenType::LeftParen,
+            &format!("expected '(' after {kind} name"),
+        )?;
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGS {
+                    let e = self.error_at_current(
+                        ErrorCode::TooManyArguments,
+                        &format!("can't have more than {MAX_ARGS} parameters"),
+                    );
+                    self.errors.push(e);
+                }
+                params.push(self.consume(TokenType::Identifier, "expected parameter name")?);
+                if self.advance_if_eq(&[TokenType::Comma]).is_none() {
+                    break;
                 }
             }
         }
-        None
+        self.consume(TokenType::RightParen, "expected ')' after parameters")?;
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("expected '{{' before {kind} body"),
+        )?;
+        let body = self.block()?;
+        Ok(FunctionDecl { name, params, body })
+    }
+
+    // Consumes `var`, or `let` if the dialect has it. `let` only counts when a
+    // name follows, so that `let = 1;` still assigns to a variable.
+    fn variable_keyword(&mut self) -> bool {
+        if self.advance_if_eq(&[TokenType::Var]).is_some() {
+            return true;
+        }
+        let dialect = self.dialect;
+        let is_let = self.current().is_some_and(|cur| {
+            cur.kind == TokenType::Identifier && dialect.is_contextual(cur.lexeme, TokenType::Let)
+        }) && self
+            .tokens
+            .peek()
+            .is_some_and(|next| next.kind == TokenType::Identifier);
+        if is_let {
+            self.advance();
+        }
+        is_let
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        let name = self.consume(TokenType::Identifier, "expected variable name")?;
+        let initializer = match self.advance_if_eq(&[TokenType::Equal]) {
+            Some(_) => Some(self.expression()?),
+            None => None,
+        };
+        self.consume(
+            TokenType::Semicolon,
+            "expected ';' after variable declaration",
+        )?;
+        Ok(Stmt::Var { name, initializer })
+    }
+
+    fn statement(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        if self.advance_if_eq(&[TokenType::Print]).is_some() {
+            let value = self.expression()?;
+            self.consume(TokenType::Semicolon, "expected ';' after value")?;
+            Ok(Stmt::Print(value))
+        } else if self.advance_if_eq(&[TokenType::LeftBrace]).is_some() {
+            Ok(Stmt::Block(self.block()?))
+        } else if self.advance_if_eq(&[TokenType::If]).is_some() {
+            self.if_statement()
+        } else if self.advance_if_eq(&[TokenType::While]).is_some() {
+            self.while_statement()
+        } else if self.advance_if_eq(&[TokenType::For]).is_some() {
+            self.for_statement()
+        } else if let Some(keyword) = self.advance_if_eq(&[TokenType::Return]) {
+            let value = if self.check(TokenType::Semicolon) {
+                None
+            } else {
+                Some(self.expression()?)
+            };
+            self.consume(TokenType::Semicolon, "expected ';' after return value")?;
+            Ok(Stmt::Return { keyword, value })
+        } else if let Some(keyword) = self.unsupported_keyword() {
+            Err(self.error_at_current(
+                ErrorCode::UnsupportedSyntax,
+                &format!("'{keyword}' statements are not supported yet"),
+            ))
+        } else {
+            let expr = self.expression()?;
+            self.consume(TokenType::Semicolon, "expected ';' after expression")?;
+            Ok(Stmt::Expression(expr))
+        }
+    }
+
+    // A keyword the dialect has but that no statement is parsed for yet.
+    // `import` is contextual, so it only counts when a name follows.
+    fn unsupported_keyword(&mut self) -> Option<&'src str> {
+        let cur = self.current()?;
+        let lexeme = cur.lexeme;
+        let unsupported = match cur.kind {
+            TokenType::Break | TokenType::Continue => true,
+            TokenType::Identifier => {
+                self.dialect.is_contextual(lexeme, TokenType::Import)
+                    && self.tokens.peek().is_some_and(|next| {
+                        matches!(next.kind, TokenType::Identifier | TokenType::StrLiteral)
+                    })
+            }
+            _ => false,
+        };
+        unsupported.then_some(lexeme)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        self.consume(TokenType::LeftParen, "expected '(' after 'if'")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "expected ')' after if condition")?;
+        let then_branch = Box::new(self.statement()?);
+        // An `else` binds to the nearest preceding `if`
+        let else_branch = match self.advance_if_eq(&[TokenType::Else]) {
+            Some(_) => Some(Box::new(self.statement()?)),
+            None => None,
+        };
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        self.consume(TokenType::LeftParen, "expected '(' after 'while'")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "expected ')' after condition")?;
+        let body = Box::new(self.statement()?);
+        Ok(Stmt::While { condition, body })
     }
 
-    fn expression(&mut self) -> Expression {
+    // Desugars `for (init; cond; incr) body` into
+    // `{ init; while (cond) { body; incr; } }`
+    fn for_statement(&mut self) -> Result<Stmt<'src>, ParseError<'src>> {
+        self.consume(TokenType::LeftParen, "expected '(' after 'for'")?;
+        let initializer = if self.advance_if_eq(&[TokenType::Semicolon]).is_some() {
+            None
+        } else if self.variable_keyword() {
+            Some(self.var_declaration()?)
+        } else {
+            let expr = self.expression()?;
+            self.consume(TokenType::Semicolon, "expected ';' after loop initializer")?;
+            Some(Stmt::Expression(expr))
+        };
+        let condition = match self.current() {
+            Some(cur) if cur.kind == TokenType::Semicolon => {
+                Expression::Literal(Literal::Bool(true), cur.span)
+            }
+            _ => self.expression()?,
+        };
+        self.consume(TokenType::Semicolon, "expected ';' after loop condition")?;
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "expected ')' after for clauses")?;
+
+        let mut body = self.statement()?;
+        if let Some(increment) = increment {
+            body = Stmt::Block(vec![body, Stmt::Expression(increment)]);
+        }
+        body = Stmt::While {
+            condition,
+            body: Box::new(body),
+        };
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body]);
+        }
+        Ok(body)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt<'src>>, ParseError<'src>> {
+        let mut statements = Vec::new();
+        while !self.is_at_end() && !self.check(TokenType::RightBrace) {
+            statements.extend(self.declaration());
+        }
+        self.consume(TokenType::RightBrace, "expected '}' after block")?;
+        Ok(statements)
+    }
+
+    fn expression(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
+        let expr = self.or()?;
+        if let Some(equals) = self.advance_if_eq(&[TokenType::Equal]) {
+            let value = self.assignment()?;
+            return Ok(match expr {
+                Expression::Variable { name, .. } => Expression::Assign {
+                    name,
+                    value: Box::new(value),
+                    depth: Cell::new(None),
+                },
+                Expression::Get { object, name } => Expression::Set {
+                    object,
+                    name,
+                    value: Box::new(value),
+                },
+                // Reported without unwinding since the parser isn't confused
+                _ => {
+                    self.errors.push(ParseError {
+                        token: equals,
+                        code: ErrorCode::InvalidAssignmentTarget,
+                        message: "invalid assignment target".to_owned(),
+                        found: false,
+                        related: Some(Box::new(Label::new(expr.span(), "cannot be assigned to"))),
+                    });
+                    expr
+                }
+            });
+        }
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
+        let mut expr = self.and()?;
+        while let Some(op) = self.advance_if_eq(&[TokenType::Or]) {
+            let right = self.and()?;
+            expr = Expression::Logical {
+                e1: Box::new(expr),
+                op,
+                e2: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
+        let mut expr = self.equality()?;
+        while let Some(op) = self.advance_if_eq(&[TokenType::And]) {
+            let right = self.equality()?;
+            expr = Expression::Logical {
+                e1: Box::new(expr),
+                op,
+                e2: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
         let options = vec![TokenType::NotEqual, TokenType::EqualEqual];
 
-        let mut expr = self.comparison();
+        let mut expr = self.comparison()?;
         while let Some(op) = self.advance_if_eq(&options) {
-            let right = self.comparison();
+            let right = self.comparison()?;
             expr = Expression::Binary {
                 e1: Box::new(expr),
                 op,
                 e2: Box::new(right),
             };
         }
-        expr
+        Ok(expr)
     }
 
-    fn comparison(&mut self) -> Expression {
+    fn comparison(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
         let options = vec![
             TokenType::Greater,
             TokenType::GreaterEqual,
@@ -79,125 +658,488 @@ impl Parser {
             TokenType::LessEqual,
         ];
 
-        let mut expr = self.term();
+        let mut expr = self.term()?;
         while let Some(op) = self.advance_if_eq(&options) {
-            let right = self.term();
+            let right = self.term()?;
             expr = Expression::Binary {
                 e1: Box::new(expr),
                 op,
                 e2: Box::new(right),
             };
         }
-        expr
+        Ok(expr)
     }
 
-    fn term(&mut self) -> Expression {
+    fn term(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
         let options = vec![TokenType::Plus, TokenType::Minus];
 
-        let mut expr = self.factor();
+        let mut expr = self.factor()?;
         while let Some(op) = self.advance_if_eq(&options) {
-            let right = self.factor();
+            let right = self.factor()?;
             expr = Expression::Binary {
                 e1: Box::new(expr),
                 op,
                 e2: Box::new(right),
             };
         }
-        expr
+        Ok(expr)
     }
 
-    fn factor(&mut self) -> Expression {
+    fn factor(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
         let options = vec![TokenType::Times, TokenType::Divide];
 
-        let mut expr = self.unary();
+        let mut expr = self.unary()?;
         while let Some(op) = self.advance_if_eq(&options) {
-            let right = self.unary();
+            let right = self.unary()?;
             expr = Expression::Binary {
                 e1: Box::new(expr),
                 op,
                 e2: Box::new(right),
             };
         }
-        expr
+        Ok(expr)
     }
 
-    fn unary(&mut self) -> Expression {
+    fn unary(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
         let options = vec![TokenType::Minus, TokenType::Not];
 
         if let Some(op) = self.advance_if_eq(&options) {
-            let right = self.unary();
-            Expression::Unary {
+            let right = self.unary()?;
+            Ok(Expression::Unary {
                 op,
                 e: Box::new(right),
-            }
+            })
         } else {
-            self.primary()
+            self.call()
         }
     }
 
-    fn primary(&mut self) -> Expression {
-        let expr = match self.current() {
-            Some(cur) => match cur.kind {
-                TokenType::NumLiteral => {
-                    let num = cur.lexeme.parse().expect("Failed to parse number");
-                    Expression::Literal(Literal::Num(num))
+    fn call(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
+        let mut expr = self.primary()?;
+        while let Some(token) = self.advance_if_eq(&[TokenType::LeftParen, TokenType::Dot]) {
+            expr = if token.kind == TokenType::LeftParen {
+                self.finish_call(expr)?
+            } else {
+                let name =
+                    self.consume(TokenType::Identifier, "expected property name after '.'")?;
+                Expression::Get {
+                    object: Box::new(expr),
+                    name,
                 }
-                TokenType::StrLiteral => {
-                    let s = cur.lexeme.clone();
-                    Expression::Literal(Literal::Str(s))
+            };
+        }
+        Ok(expr)
+    }
+
+    fn finish_call(
+        &mut self,
+        callee: Expression<'src>,
+    ) -> Result<Expression<'src>, ParseError<'src>> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGS {
+                    let e = self.error_at_current(
+                        ErrorCode::TooManyArguments,
+                        &format!("can't have more than {MAX_ARGS} arguments"),
+                    );
+                    self.errors.push(e);
                 }
-                TokenType::True => Expression::Literal(Literal::Bool(true)),
-                TokenType::False => Expression::Literal(Literal::Bool(false)),
-                TokenType::Nil => Expression::Literal(Literal::Nil),
-                TokenType::LeftParen => {
-                    self.advance(); // left paren
-                    let inner = self.expression();
-                    if self.advance_if_eq(&vec![TokenType::RightParen]) == None {
-                        panic!("Error: no matching right parenthesis");
-                    }
-                    return inner;
+                arguments.push(self.expression()?);
+                if self.advance_if_eq(&[TokenType::Comma]).is_none() {
+                    break;
                 }
-                _ => panic!("Expected literal, found wrong TokenType"),
+            }
+        }
+        let paren = self.consume(TokenType::RightParen, "expected ')' after arguments")?;
+        Ok(Expression::Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        })
+    }
+
+    // The scanner splits `"a ${x} b ${y} c"` into the fragment tokens
+    // `"a ${`, `} b ${` and `} c"` with each expression's tokens in between
+    fn interpolation(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
+        let mut parts = Vec::new();
+        while let Some(fragment) = self.advance_if_eq(&[TokenType::Interpolation]) {
+            parts.push(literal(&fragment));
+            // Fragments resuming a string begin with the `}` closing `${`
+            if self
+                .current()
+                .is_some_and(|cur| cur.lexeme.starts_with('}'))
+            {
+                return Err(self.expected(
+                    ErrorCode::ExpectedExpression,
+                    "expected expression inside '${}'",
+                ));
+            }
+            parts.push(self.expression()?);
+        }
+        let tail = self.consume(
+            TokenType::StrLiteral,
+            "expected '}' after interpolated expression",
+        )?;
+        parts.push(literal(&tail));
+        Ok(Expression::Interpolation(parts))
+    }
+
+    fn primary(&mut self) -> Result<Expression<'src>, ParseError<'src>> {
+        let Some(cur) = self.current() else {
+            return Err(self.expected(ErrorCode::ExpectedExpression, "expected expression"));
+        };
+        let expr = match cur.kind {
+            TokenType::NumLiteral | TokenType::StrLiteral => literal(cur),
+            TokenType::Interpolation => return self.interpolation(),
+            TokenType::True => Expression::Literal(Literal::Bool(true), cur.span),
+            TokenType::False => Expression::Literal(Literal::Bool(false), cur.span),
+            TokenType::Nil => Expression::Literal(Literal::Nil, cur.span),
+            TokenType::Identifier => Expression::Variable {
+                name: cur.clone(),
+                depth: Cell::new(None),
+            },
+            TokenType::This => Expression::This {
+                keyword: cur.clone(),
+                depth: Cell::new(None),
             },
-            None => panic!("Expected literal, found EOF"),
+            TokenType::Super => {
+                let keyword = cur.clone();
+                self.advance();
+                self.consume(TokenType::Dot, "expected '.' after 'super'")?;
+                let method =
+                    self.consume(TokenType::Identifier, "expected superclass method name")?;
+                return Ok(Expression::Super {
+                    keyword,
+                    method,
+                    depth: Cell::new(None),
+                });
+            }
+            TokenType::LeftParen => {
+                let paren = cur.span;
+                self.advance(); // left paren
+                let inner = self.expression()?;
+                self.consume(TokenType::RightParen, "expected ')' after expression")
+                    .map_err(|e| e.with_related(paren, "unclosed '(' opened here"))?;
+                return Ok(inner);
+            }
+            _ => return Err(self.expected(ErrorCode::ExpectedExpression, "expected expression")),
         };
         self.advance();
-        expr
+        Ok(expr)
+    }
+}
+
+fn literal<'src>(token: &Token<'src>) -> Expression<'src> {
+    let literal = token
+        .literal
+        .clone()
+        .expect("literal token without a value");
+    Expression::Literal(literal, token.span)
+}
+
+// Classifies a token that `consume` expected but didn't find
+fn missing_token_code(kind: TokenType) -> ErrorCode {
+    match kind {
+        TokenType::Semicolon => ErrorCode::MissingSemicolon,
+        TokenType::Identifier => ErrorCode::ExpectedName,
+        // A string fragment is what follows the `}` closing an interpolation
+        TokenType::RightParen | TokenType::RightBrace | TokenType::StrLiteral => {
+            ErrorCode::UnclosedDelimiter
+        }
+        _ => ErrorCode::ExpectedToken,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::keywords::Extension;
+    use crate::scanner::Scanner;
+    use std::fs;
 
     #[test]
     fn test1() {
         let tokens = vec![
             Token {
                 kind: TokenType::NumLiteral,
-                lexeme: "6".to_owned(),
+                lexeme: "6",
                 line: 1,
+                column: 1,
+                span: Span::new(0, 1),
+                literal: Some(Literal::Num(6.0)),
+                symbol: None,
             },
             Token {
                 kind: TokenType::Divide,
-                lexeme: "/".to_owned(),
+                lexeme: "/",
                 line: 1,
+                column: 3,
+                span: Span::new(2, 3),
+                literal: None,
+                symbol: None,
             },
             Token {
                 kind: TokenType::NumLiteral,
-                lexeme: "3".to_owned(),
+                lexeme: "3",
                 line: 1,
+                column: 5,
+                span: Span::new(4, 5),
+                literal: Some(Literal::Num(3.0)),
+                symbol: None,
             },
         ];
         let mut parser = Parser::new(tokens);
-        let expr = parser.parse();
+        let expr = parser.parse().unwrap();
         assert_eq!(
             expr,
             Expression::Binary {
-                e1: Box::new(Expression::Literal(Literal::Num(6f64))),
-                op: TokenType::Divide,
-                e2: Box::new(Expression::Literal(Literal::Num(3f64))),
+                e1: Box::new(Expression::Literal(Literal::Num(6f64), Span::new(0, 1))),
+                op: Token {
+                    kind: TokenType::Divide,
+                    lexeme: "/",
+                    line: 1,
+                    column: 3,
+                    span: Span::new(2, 3),
+                    literal: None,
+                    symbol: None,
+                },
+                e2: Box::new(Expression::Literal(Literal::Num(3f64), Span::new(4, 5))),
+            }
+        );
+        assert_eq!(expr.span(), Span::new(0, 5));
+    }
+
+    #[test]
+    fn test2() {
+        let source = fs::read_to_string("test3.lox").expect("Failed to read file");
+        let tokens = Scanner::new(&source).scan_tokens().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        assert_eq!(program.len(), 4);
+        assert!(matches!(
+            &program[2],
+            Stmt::Var { name, initializer: Some(Expression::Binary { .. }) } if name.lexeme == "c"
+        ));
+        assert!(
+            matches!(&program[3], Stmt::Print(Expression::Variable { name, .. }) if name.lexeme == "c")
+        );
+    }
+
+    #[test]
+    fn test3() {
+        let tokens = Scanner::new("{ a = b = 1; }").scan_tokens().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let Stmt::Block(body) = &program[0] else {
+            panic!("expected block, found {:?}", program[0]);
+        };
+        let Stmt::Expression(Expression::Assign { name, value, .. }) = &body[0] else {
+            panic!("expected assignment, found {:?}", body[0]);
+        };
+        assert_eq!(name.lexeme, "a");
+        assert!(matches!(**value, Expression::Assign { .. }));
+    }
+
+    #[test]
+    fn test4() {
+        let tokens = Scanner::new("if (a) if (b) c; else d;")
+            .scan_tokens()
+            .unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let Stmt::If {
+            then_branch,
+            else_branch: None,
+            ..
+        } = &program[0]
+        else {
+            panic!("expected if without else, found {:?}", program[0]);
+        };
+        assert!(matches!(
+            **then_branch,
+            Stmt::If {
+                else_branch: Some(_),
+                ..
             }
+        ));
+    }
+
+    #[test]
+    fn test5() {
+        let tokens = Scanner::new("for (var i = 0; i < 3; i = i + 1) print i;")
+            .scan_tokens()
+            .unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let Stmt::Block(outer) = &program[0] else {
+            panic!("expected block, found {:?}", program[0]);
+        };
+        assert!(matches!(outer[0], Stmt::Var { .. }));
+        let Stmt::While { body, .. } = &outer[1] else {
+            panic!("expected while, found {:?}", outer[1]);
+        };
+        assert!(matches!(&**body, Stmt::Block(inner) if inner.len() == 2));
+    }
+
+    #[test]
+    fn test6() {
+        let tokens = Scanner::new("fun add(a, b) { return a + b; } add(1, 2)(3);")
+            .scan_tokens()
+            .unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let Stmt::Function(decl) = &program[0] else {
+            panic!("expected function, found {:?}", program[0]);
+        };
+        assert_eq!(decl.name.lexeme, "add");
+        assert_eq!(decl.params.len(), 2);
+        assert!(matches!(decl.body[0], Stmt::Return { value: Some(_), .. }));
+        let Stmt::Expression(Expression::Call {
+            callee, arguments, ..
+        }) = &program[1]
+        else {
+            panic!("expected call, found {:?}", program[1]);
+        };
+        assert_eq!(arguments.len(), 1);
+        assert!(matches!(&**callee, Expression::Call { arguments, .. } if arguments.len() == 2));
+    }
+
+    #[test]
+    fn test7() {
+        let source = format!("f({});", vec!["1"; 256].join(", "));
+        let tokens = Scanner::new(&source).scan_tokens().unwrap();
+        let errors = Parser::new(tokens).parse_program().unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["[line 1] Error at '1': can't have more than 255 arguments"]
+        );
+    }
+
+    #[test]
+    fn test8() {
+        let source = "class B < A { init() { this.x = super.y; } }";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let Stmt::Class {
+            name,
+            superclass:
+                Some(Expression::Variable {
+                    name: superclass, ..
+                }),
+            methods,
+        } = &program[0]
+        else {
+            panic!("expected subclass, found {:?}", program[0]);
+        };
+        assert_eq!(name.lexeme, "B");
+        assert_eq!(superclass.lexeme, "A");
+        assert_eq!(methods[0].name.lexeme, "init");
+        let Stmt::Expression(Expression::Set { object, value, .. }) = &methods[0].body[0] else {
+            panic!(
+                "expected property assignment, found {:?}",
+                methods[0].body[0]
+            );
+        };
+        assert!(matches!(**object, Expression::This { .. }));
+        assert!(matches!(**value, Expression::Super { .. }));
+    }
+
+    #[test]
+    fn test9() {
+        let source = "var = 1;\nprint (2;\n1 + 2 = 3;\nfun f( { }\nprint 4;\nvar x = 5";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let errors = Parser::new(tokens).parse_program().unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "[line 1] Error at '=': expected variable name",
+                "[line 2] Error at ';': expected ')' after expression",
+                "[line 3] Error at '=': invalid assignment target",
+                "[line 4] Error at '{': expected parameter name",
+                "[line 6] Error at end: expected ';' after variable declaration",
+            ]
+        );
+        assert_eq!(
+            errors.iter().map(|e| e.code).collect::<Vec<_>>(),
+            vec![
+                ErrorCode::ExpectedName,
+                ErrorCode::UnclosedDelimiter,
+                ErrorCode::InvalidAssignmentTarget,
+                ErrorCode::ExpectedName,
+                ErrorCode::MissingSemicolon,
+            ]
+        );
+    }
+
+    #[test]
+    fn found_labels() {
+        let labels = |source| {
+            let tokens = Scanner::new(source).scan_tokens().unwrap();
+            let errors = Parser::new(tokens).parse_program().unwrap_err();
+            errors
+                .iter()
+                .map(|e| e.to_diagnostic().primary.unwrap().message)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(labels("print (2;"), vec!["found ';'"]);
+        assert_eq!(labels("print"), vec!["found end of file"]);
+        // The token is right, the expression before it isn't
+        assert_eq!(labels("1 = 2;"), vec![""]);
+    }
+
+    #[test]
+    fn contextual_let() {
+        let source = "let x = 1; let = 2; let(x); for (let i = 0; i < 1;) {}";
+        let parse = |dialect| {
+            let tokens = Scanner::new(source)
+                .with_dialect(dialect)
+                .scan_tokens()
+                .unwrap();
+            Parser::new(tokens).with_dialect(dialect).parse_program()
+        };
+        let program = parse(Dialect::default().with(Extension::Let)).unwrap();
+        assert!(matches!(&program[0], Stmt::Var { name, .. } if name.lexeme == "x"));
+        // Without a name after it, `let` is an ordinary variable
+        assert!(matches!(
+            &program[1],
+            Stmt::Expression(Expression::Assign { name, .. }) if name.lexeme == "let"
+        ));
+        assert!(matches!(
+            &program[2],
+            Stmt::Expression(Expression::Call { .. })
+        ));
+        let Stmt::Block(init) = &program[3] else {
+            panic!("expected desugared for loop, found {:?}", program[3]);
+        };
+        assert!(matches!(&init[0], Stmt::Var { name, .. } if name.lexeme == "i"));
+
+        let errors = parse(Dialect::default()).unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 1] Error at 'x': expected ';' after expression"
+        );
+    }
+
+    #[test]
+    fn unsupported_extensions() {
+        let source = "while (true) { break; continue; }\nimport \"lib\";\nimport = 1;";
+        let dialect = Dialect::default()
+            .with(Extension::LoopControl)
+            .with(Extension::Modules);
+        let tokens = Scanner::new(source)
+            .with_dialect(dialect)
+            .scan_tokens()
+            .unwrap();
+        let errors = Parser::new(tokens)
+            .with_dialect(dialect)
+            .parse_program()
+            .unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "[line 1] Error at 'break': 'break' statements are not supported yet",
+                "[line 1] Error at 'continue': 'continue' statements are not supported yet",
+                "[line 2] Error at 'import': 'import' statements are not supported yet",
+            ]
         );
+        assert!(errors
+            .iter()
+            .all(|e| e.code == ErrorCode::UnsupportedSyntax));
     }
 }
@@ -0,0 +1,428 @@
+use crate::diagnostics::{Diagnostic, Label, ToDiagnostic};
+use crate::error_code::ErrorCode;
+use crate::parser::{Expression, FunctionDecl, Stmt};
+use crate::scanner::{Span, Token};
+use crate::symbol::{self, Symbol};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(PartialEq, Debug)]
+pub struct ResolveError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub lexeme: String,
+    pub line: i32,
+    pub span: Span,
+    // Another location that helps explain the error
+    pub related: Option<Label>,
+}
+
+impl ToDiagnostic for ResolveError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(&self.message, self.span).with_code(self.code);
+        match &self.related {
+            Some(related) => diagnostic.with_secondary(related.span, &related.message),
+            None => diagnostic,
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[line {}] Error at '{}': {}",
+            self.line, self.lexeme, self.message
+        )
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub fn resolve(statements: &[Stmt<'_>]) -> Result<(), Vec<ResolveError>> {
+    let mut resolver = Resolver::new();
+    resolver.resolve_statements(statements);
+    resolver.finish()
+}
+
+pub fn resolve_expression(expr: &Expression<'_>) -> Result<(), Vec<ResolveError>> {
+    let mut resolver = Resolver::new();
+    resolver.resolve_expression(expr);
+    resolver.finish()
+}
+
+struct Local {
+    // Whether the variable's initializer has finished
+    defined: bool,
+    declared_at: Span,
+}
+
+struct Resolver {
+    scopes: Vec<HashMap<Symbol, Local>>,
+    function: FunctionType,
+    class: ClassType,
+    errors: Vec<ResolveError>,
+}
+
+impl Resolver {
+    fn new() -> Self {
+        Resolver {
+            scopes: Vec::new(),
+            function: FunctionType::None,
+            class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    fn finish(self) -> Result<(), Vec<ResolveError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn error(&mut self, token: &Token, code: ErrorCode, message: &str) {
+        self.errors.push(ResolveError {
+            code,
+            message: message.to_owned(),
+            lexeme: token.lexeme.to_owned(),
+            line: token.line,
+            span: token.span,
+            related: None,
+        });
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        let local = Local {
+            defined: false,
+            declared_at: name.span,
+        };
+        if let Some(previous) = scope.insert(name.name(), local) {
+            self.error(
+                name,
+                ErrorCode::DuplicateVariable,
+                "Already a variable with this name in this scope.",
+            );
+            let related = Label::new(previous.declared_at, "previously declared here");
+            self.errors.last_mut().unwrap().related = Some(related);
+        }
+    }
+
+    fn define(&mut self, name: Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope
+                .entry(name)
+                .or_insert(Local {
+                    defined: false,
+                    declared_at: Span::default(),
+                })
+                .defined = true;
+        }
+    }
+
+    fn resolve_local(&mut self, name: Symbol, depth: &Cell<Option<usize>>) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name));
+        depth.set(found);
+    }
+
+    fn resolve_statements(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.resolve_statement(stmt);
+        }
+    }
+
+    fn resolve_statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression(expr) | Stmt::Print(expr) => self.resolve_expression(expr),
+            Stmt::Var { name, initializer } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expression(initializer);
+                }
+                self.define(name.name());
+            }
+            Stmt::Block(statements) => {
+                self.begin_scope();
+                self.resolve_statements(statements);
+                self.end_scope();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            }
+            Stmt::While { condition, body } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(body);
+            }
+            Stmt::Function(declaration) => {
+                self.declare(&declaration.name);
+                self.define(declaration.name.name());
+                self.resolve_function(declaration, FunctionType::Function);
+            }
+            Stmt::Return { keyword, value } => {
+                if self.function == FunctionType::None {
+                    self.error(
+                        keyword,
+                        ErrorCode::TopLevelReturn,
+                        "Can't return from top-level code.",
+                    );
+                }
+                if let Some(value) = value {
+                    if self.function == FunctionType::Initializer {
+                        self.error(
+                            keyword,
+                            ErrorCode::ReturnFromInitializer,
+                            "Can't return a value from an initializer.",
+                        );
+                    }
+                    self.resolve_expression(value);
+                }
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let enclosing_class = self.class;
+                self.class = ClassType::Class;
+                self.declare(name);
+                self.define(name.name());
+
+                if let Some(superclass) = superclass {
+                    if let Expression::Variable {
+                        name: superclass_name,
+                        ..
+                    } = superclass
+                    {
+                        if superclass_name.name() == name.name() {
+                            self.error(
+                                superclass_name,
+                                ErrorCode::InheritFromSelf,
+                                "A class can't inherit from itself.",
+                            );
+                            let related = Label::new(name.span, "class declared here");
+                            self.errors.last_mut().unwrap().related = Some(related);
+                        }
+                    }
+                    self.class = ClassType::Subclass;
+                    self.resolve_expression(superclass);
+                    self.begin_scope();
+                    self.define(*symbol::SUPER);
+                }
+
+                self.begin_scope();
+                self.define(*symbol::THIS);
+                for method in methods {
+                    let kind = if method.name.name() == *symbol::INIT {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.resolve_function(method, kind);
+                }
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+                self.class = enclosing_class;
+            }
+        }
+    }
+
+    fn resolve_function(&mut self, declaration: &FunctionDecl, kind: FunctionType) {
+        let enclosing_function = self.function;
+        self.function = kind;
+        self.begin_scope();
+        for param in &declaration.params {
+            self.declare(param);
+            self.define(param.name());
+        }
+        self.resolve_statements(&declaration.body);
+        self.end_scope();
+        self.function = enclosing_function;
+    }
+
+    fn resolve_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Literal(..) => {}
+            Expression::Unary { e, .. } => self.resolve_expression(e),
+            Expression::Binary { e1, e2, .. } | Expression::Logical { e1, e2, .. } => {
+                self.resolve_expression(e1);
+                self.resolve_expression(e2);
+            }
+            Expression::Variable { name, depth } => {
+                let scope = self.scopes.last();
+                if scope
+                    .and_then(|scope| scope.get(&name.name()))
+                    .is_some_and(|local| !local.defined)
+                {
+                    self.error(
+                        name,
+                        ErrorCode::ReadInOwnInitializer,
+                        "Can't read local variable in its own initializer.",
+                    );
+                }
+                self.resolve_local(name.name(), depth);
+            }
+            Expression::Assign { name, value, depth } => {
+                self.resolve_expression(value);
+                self.resolve_local(name.name(), depth);
+            }
+            Expression::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expression(callee);
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            Expression::Get { object, .. } => self.resolve_expression(object),
+            Expression::Interpolation(parts) => {
+                for part in parts {
+                    self.resolve_expression(part);
+                }
+            }
+            Expression::Set { object, value, .. } => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+            }
+            Expression::This { keyword, depth } => {
+                if self.class == ClassType::None {
+                    self.error(
+                        keyword,
+                        ErrorCode::ThisOutsideClass,
+                        "Can't use 'this' outside of a class.",
+                    );
+                    return;
+                }
+                self.resolve_local(keyword.name(), depth);
+            }
+            Expression::Super { keyword, depth, .. } => {
+                match self.class {
+                    ClassType::None => self.error(
+                        keyword,
+                        ErrorCode::SuperOutsideClass,
+                        "Can't use 'super' outside of a class.",
+                    ),
+                    ClassType::Class => self.error(
+                        keyword,
+                        ErrorCode::SuperWithoutSuperclass,
+                        "Can't use 'super' in a class with no superclass.",
+                    ),
+                    ClassType::Subclass => {}
+                }
+                self.resolve_local(keyword.name(), depth);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn errors(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        match resolve(&program) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn depths() {
+        let source = "var a = 1; { var b = a; { b = b; } }";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        assert_eq!(resolve(&program), Ok(()));
+
+        let Stmt::Block(outer) = &program[1] else {
+            panic!("expected block, found {:?}", program[1]);
+        };
+        let Stmt::Var {
+            initializer: Some(Expression::Variable { depth, .. }),
+            ..
+        } = &outer[0]
+        else {
+            panic!("expected var, found {:?}", outer[0]);
+        };
+        assert_eq!(depth.get(), None);
+        let Stmt::Block(inner) = &outer[1] else {
+            panic!("expected block, found {:?}", outer[1]);
+        };
+        let Stmt::Expression(Expression::Assign { value, depth, .. }) = &inner[0] else {
+            panic!("expected assignment, found {:?}", inner[0]);
+        };
+        assert_eq!(depth.get(), Some(1));
+        assert!(matches!(&**value, Expression::Variable { depth, .. } if depth.get() == Some(1)));
+    }
+
+    #[test]
+    fn static_errors() {
+        assert_eq!(
+            errors("{ var a = a; }"),
+            vec!["[line 1] Error at 'a': Can't read local variable in its own initializer."]
+        );
+        assert_eq!(
+            errors("fun f(a) { var b; var b; }"),
+            vec!["[line 1] Error at 'b': Already a variable with this name in this scope."]
+        );
+        assert_eq!(
+            errors("return 1;\nprint this;\nclass A { init() { return 1; } }"),
+            vec![
+                "[line 1] Error at 'return': Can't return from top-level code.",
+                "[line 2] Error at 'this': Can't use 'this' outside of a class.",
+                "[line 3] Error at 'return': Can't return a value from an initializer.",
+            ]
+        );
+        assert_eq!(
+            errors("class A < A { f() { super.f(); } }\nclass B { f() { super.f(); } }"),
+            vec![
+                "[line 1] Error at 'A': A class can't inherit from itself.",
+                "[line 2] Error at 'super': Can't use 'super' in a class with no superclass.",
+            ]
+        );
+        assert_eq!(errors("var a = 1; var a = a;"), Vec::<String>::new());
+    }
+}
@@ -1,3 +1,10 @@
+use crate::diagnostics::{Diagnostic, ToDiagnostic};
+use crate::error_code::ErrorCode;
+use crate::keywords::Dialect;
+use crate::parser::Literal;
+use crate::symbol::Symbol;
+use std::collections::VecDeque;
+use std::fmt;
 use std::iter::Peekable;
 use std::str::Chars;
 use unicode_xid::UnicodeXID;
@@ -29,6 +36,8 @@ pub enum TokenType {
     // Literals.
     Identifier,
     StrLiteral,
+    // A string fragment ending in `${`; the embedded expression's tokens follow
+    Interpolation,
     NumLiteral,
 
     // Keywords.
@@ -49,61 +58,376 @@ pub enum TokenType {
     Var,
     While,
 
+    // Keywords from language extensions.
+    Break,
+    Continue,
+    Let,
+    Import,
+
     Whitespace,
     Eof,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Token {
+// Byte offsets into the source, end exclusive
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    // The smallest span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+// Borrows its lexeme from the source it was scanned from
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token<'src> {
     pub kind: TokenType,
-    pub lexeme: String,
+    pub lexeme: &'src str,
+    pub line: i32,
+    pub column: usize,
+    pub span: Span,
+    // The decoded value of a literal token
+    pub literal: Option<Literal>,
+    // The interned name of an identifier or keyword
+    pub symbol: Option<Symbol>,
+}
+
+impl Token<'_> {
+    pub fn name(&self) -> Symbol {
+        self.symbol
+            .expect("only identifiers and keywords have names")
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TriviaKind {
+    // A run of spaces, tabs and other non-newline whitespace
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+    // Source that failed to scan, kept so nothing is lost
+    Skipped,
+}
+
+// Source text between tokens that doesn't affect the program
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trivia<'src> {
+    pub kind: TriviaKind,
+    pub text: &'src str,
+    pub span: Span,
+}
+
+// A token with the trivia around it. Trailing trivia runs up to and including
+// the end of the token's line; everything else before a token is leading.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TokenWithTrivia<'src> {
+    pub leading: Vec<Trivia<'src>>,
+    pub token: Token<'src>,
+    pub trailing: Vec<Trivia<'src>>,
+}
+
+impl fmt::Display for TokenWithTrivia<'_> {
+    // Writes the exact source text the token was scanned from
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for trivia in &self.leading {
+            f.write_str(trivia.text)?;
+        }
+        f.write_str(self.token.lexeme)?;
+        for trivia in &self.trailing {
+            f.write_str(trivia.text)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ScanErrorKind {
+    UnexpectedCharacter(char),
+    UnterminatedString,
+    InvalidEscape(char),
+    InvalidUnicodeEscape,
+    UnterminatedInterpolation,
+    InvalidDigit { digit: char, radix: u32 },
+    MissingDigits { radix: u32 },
+    MissingExponent,
+    MisplacedSeparator,
+    UnterminatedComment,
+}
+
+fn radix_name(radix: u32) -> &'static str {
+    match radix {
+        2 => "binary",
+        8 => "octal",
+        16 => "hexadecimal",
+        _ => "decimal",
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScanError {
+    pub kind: ScanErrorKind,
     pub line: i32,
+    pub column: usize,
+    pub span: Span,
+}
+
+impl ScanError {
+    pub fn code(&self) -> ErrorCode {
+        match self.kind {
+            ScanErrorKind::UnexpectedCharacter(_) => ErrorCode::UnexpectedCharacter,
+            ScanErrorKind::UnterminatedString => ErrorCode::UnterminatedString,
+            ScanErrorKind::InvalidEscape(_) => ErrorCode::InvalidEscape,
+            ScanErrorKind::InvalidUnicodeEscape => ErrorCode::InvalidUnicodeEscape,
+            ScanErrorKind::UnterminatedInterpolation => ErrorCode::UnterminatedInterpolation,
+            ScanErrorKind::InvalidDigit { .. }
+            | ScanErrorKind::MissingDigits { .. }
+            | ScanErrorKind::MissingExponent
+            | ScanErrorKind::MisplacedSeparator => ErrorCode::InvalidNumber,
+            ScanErrorKind::UnterminatedComment => ErrorCode::UnterminatedComment,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self.kind {
+            ScanErrorKind::UnexpectedCharacter(c) => format!("unexpected character {c:?}"),
+            ScanErrorKind::UnterminatedString => "unterminated string".to_owned(),
+            ScanErrorKind::InvalidEscape(c) => {
+                format!("unknown escape sequence '\\{}'", c.escape_debug())
+            }
+            ScanErrorKind::InvalidUnicodeEscape => "invalid unicode escape".to_owned(),
+            ScanErrorKind::UnterminatedInterpolation => "unterminated interpolation".to_owned(),
+            ScanErrorKind::InvalidDigit { digit, radix } => {
+                format!("invalid digit {digit:?} in {} literal", radix_name(radix))
+            }
+            ScanErrorKind::MissingDigits { radix } => {
+                format!("missing digits in {} literal", radix_name(radix))
+            }
+            ScanErrorKind::MissingExponent => "missing digits in exponent".to_owned(),
+            ScanErrorKind::MisplacedSeparator => "misplaced digit separator".to_owned(),
+            ScanErrorKind::UnterminatedComment => "unterminated block comment".to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[line {}, column {}] Error: {}",
+            self.line,
+            self.column,
+            self.message()
+        )
+    }
+}
+
+impl ToDiagnostic for ScanError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(&self.message(), self.span).with_code(self.code());
+        match self.kind {
+            ScanErrorKind::UnexpectedCharacter(_) => {
+                diagnostic.with_label("not valid anywhere in a Lox program")
+            }
+            ScanErrorKind::UnterminatedString => diagnostic
+                .with_label("string starts here")
+                .with_note("strings may span lines but must end with a closing '\"'"),
+            ScanErrorKind::InvalidEscape(_) => diagnostic
+                .with_label("unknown escape")
+                .with_note("valid escapes are \\n, \\r, \\t, \\0, \\\", \\\\, \\$ and \\u{...}"),
+            ScanErrorKind::InvalidUnicodeEscape => diagnostic
+                .with_label("expected 1 to 6 hex digits naming a unicode scalar value")
+                .with_note("unicode escapes are written like \\u{1F600}"),
+            ScanErrorKind::UnterminatedInterpolation => diagnostic
+                .with_label("interpolation starts here")
+                .with_note("an expression embedded with '${' must be closed by a matching '}'"),
+            ScanErrorKind::InvalidDigit { radix, .. } => {
+                diagnostic.with_label(&format!("not a {} digit", radix_name(radix)))
+            }
+            ScanErrorKind::MissingDigits { .. } => {
+                diagnostic.with_label("expected digits after the prefix")
+            }
+            ScanErrorKind::MissingExponent => diagnostic
+                .with_label("exponent has no digits")
+                .with_note("exponents are written like 1.5e-3"),
+            ScanErrorKind::MisplacedSeparator => diagnostic
+                .with_label("'_' must be followed by a digit")
+                .with_note("separators go between digits, as in 1_000_000"),
+            ScanErrorKind::UnterminatedComment => diagnostic
+                .with_label("comment starts here")
+                .with_note("block comments nest, so each '/*' needs its own '*/'"),
+        }
+    }
 }
 
-pub struct Scanner<'a> {
-    source: &'a str,
-    iter: Peekable<Chars<'a>>,
-    tokens: Vec<Token>,
+// Produces tokens on demand as an iterator; `scan_all` and friends collect
+// the whole source at once
+pub struct Scanner<'src> {
+    source: &'src str,
+    iter: Peekable<Chars<'src>>,
+    dialect: Dialect,
+    // Errors found but not yet handed out
+    errors: VecDeque<ScanError>,
+    // A token held back until the errors found while scanning it are out
+    pending: Option<Token<'src>>,
+    done: bool,
     start: usize,
     current: usize,
     line: i32,
+    // Byte offset of the first character on the current line
+    line_start: usize,
+    // An offset on the current line and its column, so each token's column
+    // is counted on from the previous one rather than from the line start
+    column_mark: (usize, usize),
+    // Interpolated expressions currently being scanned, innermost last
+    interpolations: Vec<Interpolation>,
+}
+
+struct Interpolation {
+    // Braces opened inside the expression and not yet closed
+    braces: usize,
+    unclosed: ScanError,
 }
 
-impl<'a> Scanner<'a> {
-    pub fn new(source: &str) -> Scanner {
+impl<'src> Scanner<'src> {
+    pub fn new(source: &'src str) -> Self {
         Scanner {
             source,
             iter: source.chars().peekable(),
-            tokens: Vec::new(),
+            dialect: Dialect::default(),
+            errors: VecDeque::new(),
+            pending: None,
+            done: false,
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            column_mark: (0, 1),
+            interpolations: Vec::new(),
+        }
+    }
+
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token<'src>>, Vec<ScanError>> {
+        let (tokens, errors) = self.scan_all();
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Scans the whole source, skipping past bad input so that every lexical
+    // error is reported along with the tokens that could be produced
+    pub fn scan_all(&mut self) -> (Vec<Token<'src>>, Vec<ScanError>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in self {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(e) => errors.push(e),
+            }
         }
+        (tokens, errors)
     }
 
-    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, &'static str> {
+    // Like `scan_all`, but keeps whitespace, comments and unscannable input
+    // as trivia so that the tokens reproduce the source byte for byte
+    pub fn scan_lossless(&mut self) -> (Vec<TokenWithTrivia<'src>>, Vec<ScanError>) {
+        let mut tokens: Vec<TokenWithTrivia> = Vec::new();
+        let mut leading = Vec::new();
+        // Whether trivia still belongs to the line of the last token
+        let mut trailing = false;
         loop {
-            let token = self.scan_token()?;
-            let eof = token.kind == TokenType::Eof;
-            if token.kind != TokenType::Whitespace {
-                self.tokens.push(token);
-                if eof {
-                    break;
+            let trivia = match self.scan_token() {
+                Ok(token) if token.kind == TokenType::Whitespace => {
+                    let kind = match token.lexeme.as_bytes() {
+                        [b'\n'] => TriviaKind::Newline,
+                        [b'/', b'/', ..] => TriviaKind::LineComment,
+                        [b'/', b'*', ..] => TriviaKind::BlockComment,
+                        _ => TriviaKind::Whitespace,
+                    };
+                    Trivia {
+                        kind,
+                        text: token.lexeme,
+                        span: token.span,
+                    }
+                }
+                Ok(token) => {
+                    let eof = token.kind == TokenType::Eof;
+                    tokens.push(TokenWithTrivia {
+                        leading: std::mem::take(&mut leading),
+                        token,
+                        trailing: Vec::new(),
+                    });
+                    if eof {
+                        break;
+                    }
+                    trailing = true;
+                    continue;
                 }
+                Err(e) => {
+                    self.errors.push_back(e);
+                    Trivia {
+                        kind: TriviaKind::Skipped,
+                        text: &self.source[self.start..self.current],
+                        span: Span::new(self.start, self.current),
+                    }
+                }
+            };
+            match tokens.last_mut() {
+                Some(last) if trailing => {
+                    trailing = trivia.kind != TriviaKind::Newline;
+                    last.trailing.push(trivia);
+                }
+                _ => leading.push(trivia),
             }
         }
-        Ok(self.tokens)
+        (tokens, self.errors.drain(..).collect())
     }
 
-    // Returns None for whitespace (no token)
-    fn scan_token(&mut self) -> Result<Token, &'static str> {
+    fn scan_token(&mut self) -> Result<Token<'src>, ScanError> {
         self.start = self.current;
-        let kind = if let Some(c) = self.next() {
+        let (line, column) = (self.line, self.column(self.start));
+        self.column_mark = (self.start, column);
+        let mut literal = None;
+        let mut symbol = None;
+        let kind = if let Some(c) = self.advance() {
             match c {
                 '(' => TokenType::LeftParen,
                 ')' => TokenType::RightParen,
-                '{' => TokenType::LeftBrace,
-                '}' => TokenType::RightBrace,
+                '{' => {
+                    if let Some(interpolation) = self.interpolations.last_mut() {
+                        interpolation.braces += 1;
+                    }
+                    TokenType::LeftBrace
+                }
+                '}' => match self.interpolations.last_mut() {
+                    // Closes the interpolation, resuming the enclosing string
+                    Some(Interpolation { braces: 0, .. }) => {
+                        self.interpolations.pop();
+                        let (kind, value) = self.string()?;
+                        literal = Some(Literal::Str(Symbol::intern(&value)));
+                        kind
+                    }
+                    Some(interpolation) => {
+                        interpolation.braces -= 1;
+                        TokenType::RightBrace
+                    }
+                    None => TokenType::RightBrace,
+                },
                 ',' => TokenType::Comma,
                 '.' => TokenType::Dot,
                 '-' => TokenType::Minus,
@@ -111,102 +435,128 @@ impl<'a> Scanner<'a> {
                 ';' => TokenType::Semicolon,
                 '*' => TokenType::Times,
                 '!' => {
-                    if self.next_if_eq('=') {
+                    if self.advance_if_eq('=') {
                         TokenType::NotEqual
                     } else {
                         TokenType::Not
                     }
                 }
                 '=' => {
-                    if self.next_if_eq('=') {
+                    if self.advance_if_eq('=') {
                         TokenType::EqualEqual
                     } else {
                         TokenType::Equal
                     }
                 }
                 '<' => {
-                    if self.next_if_eq('=') {
+                    if self.advance_if_eq('=') {
                         TokenType::LessEqual
                     } else {
                         TokenType::Less
                     }
                 }
                 '>' => {
-                    if self.next_if_eq('=') {
+                    if self.advance_if_eq('=') {
                         TokenType::GreaterEqual
                     } else {
                         TokenType::Greater
                     }
                 }
                 '/' => {
-                    if self.next_if_eq('/') {
-                        self.next_while(|&c| c != '\n');
+                    if self.advance_if_eq('/') {
+                        self.advance_while(|&c| c != '\n');
+                        TokenType::Whitespace
+                    } else if self.advance_if_eq('*') {
+                        self.block_comment()?;
                         TokenType::Whitespace
                     } else {
                         TokenType::Divide
                     }
                 }
                 '\n' => {
-                    self.line += 1;
+                    self.newline();
+                    TokenType::Whitespace
+                }
+                '"' => {
+                    let (kind, value) = self.string()?;
+                    literal = Some(Literal::Str(Symbol::intern(&value)));
+                    kind
+                }
+                c if c.is_whitespace() => {
+                    self.advance_while(|&c| c.is_whitespace() && c != '\n');
                     TokenType::Whitespace
                 }
-                '"' => self.string()?,
-                c if c.is_whitespace() => TokenType::Whitespace,
                 c if c.is_ascii_digit() => {
-                    self.next_while(|&c| c.is_ascii_digit());
-                    if self.decimal_point() {
-                        self.next(); // read the decimal point
-                        self.next_while(|&c| c.is_ascii_digit());
-                    }
+                    literal = Some(Literal::Num(self.number(c)?));
                     TokenType::NumLiteral
                 }
                 c if UnicodeXID::is_xid_start(c) => {
-                    self.next_while(|&c| UnicodeXID::is_xid_continue(c));
+                    self.advance_while(|&c| UnicodeXID::is_xid_continue(c));
                     // TODO: test indexing
                     let ident = &self.source[self.start..self.current];
-                    match ident {
-                        "and" => TokenType::And,
-                        "class" => TokenType::Class,
-                        "else" => TokenType::Else,
-                        "false" => TokenType::False,
-                        "for" => TokenType::For,
-                        "fun" => TokenType::Fun,
-                        "if" => TokenType::If,
-                        "nil" => TokenType::Nil,
-                        "or" => TokenType::Or,
-                        "print" => TokenType::Print,
-                        "return" => TokenType::Return,
-                        "super" => TokenType::Super,
-                        "this" => TokenType::This,
-                        "true" => TokenType::True,
-                        "var" => TokenType::Var,
-                        "while" => TokenType::While,
-                        _ => TokenType::Identifier,
-                    }
-                }
-                _ => {
-                    // TODO: more details of c and line
-                    return Err("Found unexpected character");
+                    symbol = Some(Symbol::intern(ident));
+                    self.dialect.classify(ident)
                 }
+                c => return Err(self.error(ScanErrorKind::UnexpectedCharacter(c))),
             }
         } else {
+            let unclosed = self.interpolations.drain(..).map(|i| i.unclosed);
+            self.errors.extend(unclosed);
             TokenType::Eof
         };
         let token = Token {
             kind,
-            lexeme: self.source[self.start..self.current].to_owned(),
-            line: self.line,
+            lexeme: &self.source[self.start..self.current],
+            line,
+            column,
+            span: Span::new(self.start, self.current),
+            literal,
+            symbol,
         };
         self.start = self.current;
         Ok(token)
     }
 
+    fn newline(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+        self.column_mark = (self.current, 1);
+    }
+
+    // Reports an error covering the current token so far
+    fn error(&self, kind: ScanErrorKind) -> ScanError {
+        self.error_from(self.start, kind)
+    }
+
+    fn error_from(&self, start: usize, kind: ScanErrorKind) -> ScanError {
+        self.error_at(Span::new(start, self.current), kind)
+    }
+
+    // `span` must lie on the current line
+    fn error_at(&self, span: Span, kind: ScanErrorKind) -> ScanError {
+        ScanError {
+            kind,
+            line: self.line,
+            column: self.column(span.start),
+            span,
+        }
+    }
+
+    fn column(&self, offset: usize) -> usize {
+        let (from, column) = if offset >= self.column_mark.0 {
+            self.column_mark
+        } else {
+            (self.line_start, 1)
+        };
+        self.source[from..offset].chars().count() + column
+    }
+
     fn decimal_point(&self) -> bool {
         let mut iter = self.iter.clone();
         iter.next() == Some('.') && iter.next().filter(|&c| c.is_ascii_digit()).is_some()
     }
 
-    fn next(&mut self) -> Option<char> {
+    fn advance(&mut self) -> Option<char> {
         let ret = self.iter.next();
         if let Some(c) = ret {
             self.current += c.len_utf8();
@@ -214,7 +564,7 @@ impl<'a> Scanner<'a> {
         ret
     }
 
-    fn next_if_eq(&mut self, expected: char) -> bool {
+    fn advance_if_eq(&mut self, expected: char) -> bool {
         if let Some(c) = self.iter.next_if_eq(&expected) {
             self.current += c.len_utf8();
             return true;
@@ -222,30 +572,210 @@ impl<'a> Scanner<'a> {
         false
     }
 
-    fn next_while(&mut self, p: impl FnOnce(&char) -> bool + Copy) {
+    fn advance_while(&mut self, p: impl FnOnce(&char) -> bool + Copy) {
         while let Some(c) = self.iter.next_if(p) {
             self.current += c.len_utf8();
         }
     }
 
-    fn string(&mut self) -> Result<TokenType, &'static str> {
-        while let Some(c) = self.iter.next() {
-            self.current += c.len_utf8();
-            if c == '"' {
-                return Ok(TokenType::StrLiteral);
-            } else if c == '\n' {
-                self.line += 1;
+    // Skips a `/* ... */` comment, which may contain nested block comments
+    fn block_comment(&mut self) -> Result<(), ScanError> {
+        let unterminated = self.error(ScanErrorKind::UnterminatedComment);
+        let mut depth = 1;
+        while let Some(c) = self.advance() {
+            match c {
+                '/' if self.advance_if_eq('*') => depth += 1,
+                '*' if self.advance_if_eq('/') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                '\n' => self.newline(),
+                _ => {}
+            }
+        }
+        Err(unterminated)
+    }
+
+    // Scans the rest of a number literal after its first digit. The whole
+    // literal is consumed even when malformed, so that the first problem
+    // found is the only one reported.
+    fn number(&mut self, first: char) -> Result<f64, ScanError> {
+        let radix = match (first, self.iter.peek()) {
+            ('0', Some('x' | 'X')) => 16,
+            ('0', Some('o' | 'O')) => 8,
+            ('0', Some('b' | 'B')) => 2,
+            _ => 10,
+        };
+        let mut error = None;
+        let digits = if radix == 10 {
+            self.digits(10, &mut error);
+            if self.decimal_point() {
+                self.advance(); // read the decimal point
+                self.digits(10, &mut error);
+            }
+            if self.advance_if_eq('e') || self.advance_if_eq('E') {
+                if !self.advance_if_eq('+') {
+                    self.advance_if_eq('-');
+                }
+                if self.digits(10, &mut error).is_empty() {
+                    error.get_or_insert(self.error(ScanErrorKind::MissingExponent));
+                }
+            }
+            &self.source[self.start..self.current]
+        } else {
+            self.advance(); // read the prefix
+            self.digits(radix, &mut error)
+        };
+
+        // Letters running on from the literal, as in `0b102` or `12px`
+        let rest = self.current;
+        self.advance_while(|&c| UnicodeXID::is_xid_continue(c));
+        if let Some(digit) = self.source[rest..self.current].chars().next() {
+            let span = Span::new(rest, rest + digit.len_utf8());
+            error.get_or_insert(self.error_at(span, ScanErrorKind::InvalidDigit { digit, radix }));
+        } else if digits.is_empty() {
+            error.get_or_insert(self.error(ScanErrorKind::MissingDigits { radix }));
+        }
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        let digits = digits.replace('_', "");
+        Ok(if radix == 10 {
+            digits.parse().expect("decimal literal was validated")
+        } else {
+            digits
+                .chars()
+                .filter_map(|c| c.to_digit(radix))
+                .fold(0.0, |n, d| n * radix as f64 + d as f64)
+        })
+    }
+
+    // Consumes a run of digits in `radix` and `_` separators
+    fn digits(&mut self, radix: u32, error: &mut Option<ScanError>) -> &'src str {
+        let start = self.current;
+        while let Some(c) = self.iter.next_if(|&c| c.is_digit(radix) || c == '_') {
+            self.current += 1;
+            if c == '_' && !self.iter.peek().is_some_and(|c| c.is_digit(radix)) {
+                let span = Span::new(self.current - 1, self.current);
+                error.get_or_insert(self.error_at(span, ScanErrorKind::MisplacedSeparator));
+            }
+        }
+        &self.source[start..self.current]
+    }
+
+    // Scans up to the closing quote or the next `${`, returning the contents
+    // with escapes decoded. Bad escapes are reported without ending the
+    // string.
+    fn string(&mut self) -> Result<(TokenType, String), ScanError> {
+        let unterminated = self.error(ScanErrorKind::UnterminatedString);
+        let mut value = String::new();
+        while let Some(c) = self.advance() {
+            match c {
+                '"' => return Ok((TokenType::StrLiteral, value)),
+                '$' if self.advance_if_eq('{') => {
+                    let unclosed =
+                        self.error_from(self.current - 2, ScanErrorKind::UnterminatedInterpolation);
+                    self.interpolations.push(Interpolation {
+                        braces: 0,
+                        unclosed,
+                    });
+                    return Ok((TokenType::Interpolation, value));
+                }
+                '\\' => {
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                }
+                '\n' => {
+                    self.newline();
+                    value.push(c);
+                }
+                c => value.push(c),
             }
         }
         // EOF in string
-        Err("Unterminated string")
+        Err(unterminated)
+    }
+
+    // Decodes the escape sequence following a backslash
+    fn escape(&mut self) -> Option<char> {
+        let start = self.current - 1;
+        // At EOF the string is reported as unterminated instead
+        let c = self.advance()?;
+        let decoded = match c {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '"' => '"',
+            '\\' => '\\',
+            '$' => '$',
+            'u' => return self.unicode_escape(start),
+            c => {
+                let e = self.error_from(start, ScanErrorKind::InvalidEscape(c));
+                self.errors.push_back(e);
+                if c == '\n' {
+                    self.newline();
+                }
+                return None;
+            }
+        };
+        Some(decoded)
+    }
+
+    // `\u{XXXX}` with 1 to 6 hex digits naming a unicode scalar value
+    fn unicode_escape(&mut self, start: usize) -> Option<char> {
+        let mut decoded = None;
+        if self.advance_if_eq('{') {
+            let digits_start = self.current;
+            self.advance_while(|c| c.is_ascii_hexdigit());
+            let digits = &self.source[digits_start..self.current];
+            if self.advance_if_eq('}') && (1..=6).contains(&digits.len()) {
+                decoded = u32::from_str_radix(digits, 16)
+                    .ok()
+                    .and_then(char::from_u32);
+            }
+        }
+        if decoded.is_none() {
+            let e = self.error_from(start, ScanErrorKind::InvalidUnicodeEscape);
+            self.errors.push_back(e);
+        }
+        decoded
+    }
+}
+
+impl<'src> Iterator for Scanner<'src> {
+    type Item = Result<Token<'src>, ScanError>;
+
+    // Whitespace and comments are skipped. Errors are yielded before the
+    // token they were found in, and all of them before None.
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.errors.is_empty() && self.pending.is_none() && !self.done {
+            match self.scan_token() {
+                Ok(token) if token.kind == TokenType::Whitespace => {}
+                Ok(token) => {
+                    self.done = token.kind == TokenType::Eof;
+                    self.pending = Some(token);
+                }
+                Err(e) => self.errors.push_back(e),
+            }
+        }
+        match self.errors.pop_front() {
+            Some(e) => Some(Err(e)),
+            None => self.pending.take().map(Ok),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::keywords::Extension;
     use std::fs;
+    use std::ptr;
 
     #[test]
     fn test1() {
@@ -256,33 +786,57 @@ mod tests {
             Ok(vec![
                 Token {
                     kind: TokenType::Var,
-                    lexeme: "var".to_owned(),
-                    line: 1
+                    lexeme: "var",
+                    line: 1,
+                    column: 1,
+                    span: Span::new(0, 3),
+                    literal: None,
+                    symbol: Some(Symbol::intern("var")),
                 },
                 Token {
                     kind: TokenType::Identifier,
-                    lexeme: "i".to_owned(),
-                    line: 1
+                    lexeme: "i",
+                    line: 1,
+                    column: 5,
+                    span: Span::new(4, 5),
+                    literal: None,
+                    symbol: Some(Symbol::intern("i")),
                 },
                 Token {
                     kind: TokenType::Equal,
-                    lexeme: "=".to_owned(),
-                    line: 1
+                    lexeme: "=",
+                    line: 1,
+                    column: 7,
+                    span: Span::new(6, 7),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::NumLiteral,
-                    lexeme: "1".to_owned(),
-                    line: 1
+                    lexeme: "1",
+                    line: 1,
+                    column: 9,
+                    span: Span::new(8, 9),
+                    literal: Some(Literal::Num(1.0)),
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Semicolon,
-                    lexeme: ";".to_owned(),
-                    line: 1
+                    lexeme: ";",
+                    line: 1,
+                    column: 10,
+                    span: Span::new(9, 10),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Eof,
-                    lexeme: "".to_owned(),
-                    line: 2
+                    lexeme: "",
+                    line: 2,
+                    column: 1,
+                    span: Span::new(11, 11),
+                    literal: None,
+                    symbol: None,
                 },
             ])
         );
@@ -297,33 +851,57 @@ mod tests {
             Ok(vec![
                 Token {
                     kind: TokenType::Var,
-                    lexeme: "var".to_owned(),
-                    line: 1
+                    lexeme: "var",
+                    line: 1,
+                    column: 1,
+                    span: Span::new(0, 3),
+                    literal: None,
+                    symbol: Some(Symbol::intern("var")),
                 },
                 Token {
                     kind: TokenType::Identifier,
-                    lexeme: "s".to_owned(),
-                    line: 1
+                    lexeme: "s",
+                    line: 1,
+                    column: 5,
+                    span: Span::new(4, 5),
+                    literal: None,
+                    symbol: Some(Symbol::intern("s")),
                 },
                 Token {
                     kind: TokenType::Equal,
-                    lexeme: "=".to_owned(),
-                    line: 1
+                    lexeme: "=",
+                    line: 1,
+                    column: 7,
+                    span: Span::new(6, 7),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::StrLiteral,
-                    lexeme: "\"Hello, World!\"".to_owned(),
-                    line: 1
+                    lexeme: "\"Hello, World!\"",
+                    line: 1,
+                    column: 9,
+                    span: Span::new(8, 23),
+                    literal: Some(Literal::Str(Symbol::intern("Hello, World!"))),
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Semicolon,
-                    lexeme: ";".to_owned(),
-                    line: 1
+                    lexeme: ";",
+                    line: 1,
+                    column: 24,
+                    span: Span::new(23, 24),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Eof,
-                    lexeme: "".to_owned(),
-                    line: 2
+                    lexeme: "",
+                    line: 2,
+                    column: 1,
+                    span: Span::new(25, 25),
+                    literal: None,
+                    symbol: None,
                 },
             ])
         );
@@ -338,142 +916,574 @@ mod tests {
             Ok(vec![
                 Token {
                     kind: TokenType::Var,
-                    lexeme: "var".to_owned(),
-                    line: 1
+                    lexeme: "var",
+                    line: 1,
+                    column: 1,
+                    span: Span::new(0, 3),
+                    literal: None,
+                    symbol: Some(Symbol::intern("var")),
                 },
                 Token {
                     kind: TokenType::Identifier,
-                    lexeme: "a".to_owned(),
-                    line: 1
+                    lexeme: "a",
+                    line: 1,
+                    column: 5,
+                    span: Span::new(4, 5),
+                    literal: None,
+                    symbol: Some(Symbol::intern("a")),
                 },
                 Token {
                     kind: TokenType::Equal,
-                    lexeme: "=".to_owned(),
-                    line: 1
+                    lexeme: "=",
+                    line: 1,
+                    column: 7,
+                    span: Span::new(6, 7),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::NumLiteral,
-                    lexeme: "1".to_owned(),
-                    line: 1
+                    lexeme: "1",
+                    line: 1,
+                    column: 9,
+                    span: Span::new(8, 9),
+                    literal: Some(Literal::Num(1.0)),
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Semicolon,
-                    lexeme: ";".to_owned(),
-                    line: 1
+                    lexeme: ";",
+                    line: 1,
+                    column: 10,
+                    span: Span::new(9, 10),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Var,
-                    lexeme: "var".to_owned(),
-                    line: 2
+                    lexeme: "var",
+                    line: 2,
+                    column: 1,
+                    span: Span::new(11, 14),
+                    literal: None,
+                    symbol: Some(Symbol::intern("var")),
                 },
                 Token {
                     kind: TokenType::Identifier,
-                    lexeme: "b".to_owned(),
-                    line: 2
+                    lexeme: "b",
+                    line: 2,
+                    column: 5,
+                    span: Span::new(15, 16),
+                    literal: None,
+                    symbol: Some(Symbol::intern("b")),
                 },
                 Token {
                     kind: TokenType::Equal,
-                    lexeme: "=".to_owned(),
-                    line: 2
+                    lexeme: "=",
+                    line: 2,
+                    column: 7,
+                    span: Span::new(17, 18),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::NumLiteral,
-                    lexeme: "2".to_owned(),
-                    line: 2
+                    lexeme: "2",
+                    line: 2,
+                    column: 9,
+                    span: Span::new(19, 20),
+                    literal: Some(Literal::Num(2.0)),
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Semicolon,
-                    lexeme: ";".to_owned(),
-                    line: 2
+                    lexeme: ";",
+                    line: 2,
+                    column: 10,
+                    span: Span::new(20, 21),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Var,
-                    lexeme: "var".to_owned(),
-                    line: 3
+                    lexeme: "var",
+                    line: 3,
+                    column: 1,
+                    span: Span::new(22, 25),
+                    literal: None,
+                    symbol: Some(Symbol::intern("var")),
                 },
                 Token {
                     kind: TokenType::Identifier,
-                    lexeme: "c".to_owned(),
-                    line: 3
+                    lexeme: "c",
+                    line: 3,
+                    column: 5,
+                    span: Span::new(26, 27),
+                    literal: None,
+                    symbol: Some(Symbol::intern("c")),
                 },
                 Token {
                     kind: TokenType::Equal,
-                    lexeme: "=".to_owned(),
-                    line: 3
+                    lexeme: "=",
+                    line: 3,
+                    column: 7,
+                    span: Span::new(28, 29),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Identifier,
-                    lexeme: "a".to_owned(),
-                    line: 3
+                    lexeme: "a",
+                    line: 3,
+                    column: 9,
+                    span: Span::new(30, 31),
+                    literal: None,
+                    symbol: Some(Symbol::intern("a")),
                 },
                 Token {
                     kind: TokenType::Plus,
-                    lexeme: "+".to_owned(),
-                    line: 3
+                    lexeme: "+",
+                    line: 3,
+                    column: 11,
+                    span: Span::new(32, 33),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Identifier,
-                    lexeme: "b".to_owned(),
-                    line: 3
+                    lexeme: "b",
+                    line: 3,
+                    column: 13,
+                    span: Span::new(34, 35),
+                    literal: None,
+                    symbol: Some(Symbol::intern("b")),
                 },
                 Token {
                     kind: TokenType::Times,
-                    lexeme: "*".to_owned(),
-                    line: 3
+                    lexeme: "*",
+                    line: 3,
+                    column: 15,
+                    span: Span::new(36, 37),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Identifier,
-                    lexeme: "a".to_owned(),
-                    line: 3
+                    lexeme: "a",
+                    line: 3,
+                    column: 17,
+                    span: Span::new(38, 39),
+                    literal: None,
+                    symbol: Some(Symbol::intern("a")),
                 },
                 Token {
                     kind: TokenType::Minus,
-                    lexeme: "-".to_owned(),
-                    line: 3
+                    lexeme: "-",
+                    line: 3,
+                    column: 19,
+                    span: Span::new(40, 41),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Identifier,
-                    lexeme: "b".to_owned(),
-                    line: 3
+                    lexeme: "b",
+                    line: 3,
+                    column: 21,
+                    span: Span::new(42, 43),
+                    literal: None,
+                    symbol: Some(Symbol::intern("b")),
                 },
                 Token {
                     kind: TokenType::Divide,
-                    lexeme: "/".to_owned(),
-                    line: 3
+                    lexeme: "/",
+                    line: 3,
+                    column: 23,
+                    span: Span::new(44, 45),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Identifier,
-                    lexeme: "a".to_owned(),
-                    line: 3
+                    lexeme: "a",
+                    line: 3,
+                    column: 25,
+                    span: Span::new(46, 47),
+                    literal: None,
+                    symbol: Some(Symbol::intern("a")),
                 },
                 Token {
                     kind: TokenType::Semicolon,
-                    lexeme: ";".to_owned(),
-                    line: 3
+                    lexeme: ";",
+                    line: 3,
+                    column: 26,
+                    span: Span::new(47, 48),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Print,
-                    lexeme: "print".to_owned(),
-                    line: 4
+                    lexeme: "print",
+                    line: 4,
+                    column: 1,
+                    span: Span::new(49, 54),
+                    literal: None,
+                    symbol: Some(Symbol::intern("print")),
                 },
                 Token {
                     kind: TokenType::Identifier,
-                    lexeme: "c".to_owned(),
-                    line: 4
+                    lexeme: "c",
+                    line: 4,
+                    column: 7,
+                    span: Span::new(55, 56),
+                    literal: None,
+                    symbol: Some(Symbol::intern("c")),
                 },
                 Token {
                     kind: TokenType::Semicolon,
-                    lexeme: ";".to_owned(),
-                    line: 4
+                    lexeme: ";",
+                    line: 4,
+                    column: 8,
+                    span: Span::new(56, 57),
+                    literal: None,
+                    symbol: None,
                 },
                 Token {
                     kind: TokenType::Eof,
-                    lexeme: "".to_owned(),
-                    line: 5
+                    lexeme: "",
+                    line: 5,
+                    column: 1,
+                    span: Span::new(58, 58),
+                    literal: None,
+                    symbol: None,
                 },
             ])
         );
     }
-}
 
+    #[test]
+    fn errors() {
+        let mut s = Scanner::new("var a = 1 @ 2;\n  print # a;\n\"oops\nvar");
+        let (tokens, errors) = s.scan_all();
+        assert_eq!(
+            errors,
+            vec![
+                ScanError {
+                    kind: ScanErrorKind::UnexpectedCharacter('@'),
+                    line: 1,
+                    column: 11,
+                    span: Span::new(10, 11),
+                },
+                ScanError {
+                    kind: ScanErrorKind::UnexpectedCharacter('#'),
+                    line: 2,
+                    column: 9,
+                    span: Span::new(23, 24),
+                },
+                ScanError {
+                    kind: ScanErrorKind::UnterminatedString,
+                    line: 3,
+                    column: 1,
+                    span: Span::new(28, 29),
+                },
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 1, column 11] Error: unexpected character '@'"
+        );
+        assert_eq!(tokens.len(), 10);
+        assert_eq!(tokens.last().map(|t| t.kind), Some(TokenType::Eof));
+    }
+
+    #[test]
+    fn dialects() {
+        let source = "break continue let import while";
+        let kinds = |dialect| {
+            Scanner::new(source)
+                .with_dialect(dialect)
+                .map(|t| t.unwrap().kind)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds(Dialect::default()),
+            vec![
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::While,
+                TokenType::Eof,
+            ]
+        );
+        let all = Extension::ALL
+            .into_iter()
+            .fold(Dialect::default(), Dialect::with);
+        // `let` and `import` are contextual, left for the parser to pick out
+        assert_eq!(
+            kinds(all),
+            vec![
+                TokenType::Break,
+                TokenType::Continue,
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::While,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn symbols() {
+        let tokens = Scanner::new("abc = abc + \"abc\";").scan_tokens().unwrap();
+        let abc = Symbol::intern("abc");
+        assert_eq!(tokens[0].symbol, Some(abc));
+        assert!(ptr::eq(
+            tokens[0].name().as_str(),
+            tokens[2].name().as_str()
+        ));
+        assert_eq!(tokens[1].symbol, None);
+        assert_eq!(tokens[4].literal, Some(Literal::Str(abc)));
+        assert_eq!(tokens[4].symbol, None);
+    }
+
+    #[test]
+    fn lazy() {
+        let source = "a @ b";
+        let mut s = Scanner::new(source);
+        let a = s.next().unwrap().unwrap();
+        assert_eq!((a.kind, a.lexeme), (TokenType::Identifier, "a"));
+        // Lexemes are slices of the source rather than copies
+        assert_eq!(a.lexeme.as_ptr(), source.as_ptr());
+        assert_eq!(
+            s.next().unwrap().map_err(|e| e.kind),
+            Err(ScanErrorKind::UnexpectedCharacter('@'))
+        );
+        assert_eq!(s.next().unwrap().map(|t| t.lexeme), Ok("b"));
+        assert_eq!(s.next().unwrap().map(|t| t.kind), Ok(TokenType::Eof));
+        assert_eq!(s.next(), None);
+    }
+
+    #[test]
+    fn escapes() {
+        let source = r#""a\n\t\"\\\0 \u{48}\u{1F600}" "\q\u{110000}\u{} \u41 ok""#;
+        let (tokens, errors) = Scanner::new(source).scan_all();
+        let values: Vec<_> = tokens.iter().map(|t| t.literal.clone()).collect();
+        assert_eq!(
+            values,
+            vec![
+                Some(Literal::Str(Symbol::intern("a\n\t\"\\\0 H\u{1F600}"))),
+                Some(Literal::Str(Symbol::intern(" 41 ok"))),
+                None,
+            ]
+        );
+        assert_eq!(
+            errors,
+            vec![
+                ScanError {
+                    kind: ScanErrorKind::InvalidEscape('q'),
+                    line: 1,
+                    column: 32,
+                    span: Span::new(31, 33),
+                },
+                ScanError {
+                    kind: ScanErrorKind::InvalidUnicodeEscape,
+                    line: 1,
+                    column: 34,
+                    span: Span::new(33, 43),
+                },
+                ScanError {
+                    kind: ScanErrorKind::InvalidUnicodeEscape,
+                    line: 1,
+                    column: 44,
+                    span: Span::new(43, 47),
+                },
+                ScanError {
+                    kind: ScanErrorKind::InvalidUnicodeEscape,
+                    line: 1,
+                    column: 49,
+                    span: Span::new(48, 50),
+                },
+            ]
+        );
+        assert_eq!(errors[0].message(), "unknown escape sequence '\\q'");
+    }
+
+    #[test]
+    fn interpolation() {
+        let source = r#""a ${ {"b ${y}"}.x } c\${d}""#;
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let kinds: Vec<_> = tokens
+            .iter()
+            .map(|t| (t.kind, t.lexeme, t.literal.clone()))
+            .collect();
+        let str = |s: &str| Some(Literal::Str(Symbol::intern(s)));
+        assert_eq!(
+            kinds,
+            vec![
+                (TokenType::Interpolation, "\"a ${", str("a ")),
+                (TokenType::LeftBrace, "{", None),
+                (TokenType::Interpolation, "\"b ${", str("b ")),
+                (TokenType::Identifier, "y", None),
+                (TokenType::StrLiteral, "}\"", str("")),
+                (TokenType::RightBrace, "}", None),
+                (TokenType::Dot, ".", None),
+                (TokenType::Identifier, "x", None),
+                (TokenType::StrLiteral, "} c\\${d}\"", str(" c${d}")),
+                (TokenType::Eof, "", None),
+            ]
+        );
+
+        let (_, errors) = Scanner::new("\"a ${ {").scan_all();
+        assert_eq!(
+            errors,
+            vec![ScanError {
+                kind: ScanErrorKind::UnterminatedInterpolation,
+                line: 1,
+                column: 4,
+                span: Span::new(3, 5),
+            }]
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        let source = "0 12.5 1_000_000 0xFF_ff 0o17 0b1010 1.5e-3 2E+2 3e2 4.";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let values: Vec<_> = tokens.iter().map(|t| t.literal.clone()).collect();
+        let num = |n: f64| Some(Literal::Num(n));
+        assert_eq!(
+            values,
+            vec![
+                num(0.0),
+                num(12.5),
+                num(1_000_000.0),
+                num(65535.0),
+                num(15.0),
+                num(10.0),
+                num(0.0015),
+                num(200.0),
+                num(300.0),
+                num(4.0),
+                None, // `.`
+                None,
+            ]
+        );
+
+        let source = "0b102 0x 1__0 2_ 1.5e 12px 0o8;";
+        let (tokens, errors) = Scanner::new(source).scan_all();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.message(), e.span))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "invalid digit '2' in binary literal".to_owned(),
+                    Span::new(4, 5)
+                ),
+                (
+                    "missing digits in hexadecimal literal".to_owned(),
+                    Span::new(6, 8)
+                ),
+                ("misplaced digit separator".to_owned(), Span::new(10, 11)),
+                ("misplaced digit separator".to_owned(), Span::new(15, 16)),
+                ("missing digits in exponent".to_owned(), Span::new(17, 21)),
+                (
+                    "invalid digit 'p' in decimal literal".to_owned(),
+                    Span::new(24, 25)
+                ),
+                (
+                    "invalid digit '8' in octal literal".to_owned(),
+                    Span::new(29, 30)
+                ),
+            ]
+        );
+        assert_eq!(errors[0].code(), ErrorCode::InvalidNumber);
+        // Only the `;` and Eof survive
+        assert_eq!(tokens.len(), 2);
+    }
+
+    #[test]
+    fn block_comments() {
+        let source = "a /* x /* y\n */ z */ b\n/*/ c */ d /* e\n/* f */";
+        let (tokens, errors) = Scanner::new(source).scan_all();
+        let names: Vec<_> = tokens.iter().map(|t| (t.lexeme, t.line)).collect();
+        assert_eq!(names, vec![("a", 1), ("b", 2), ("d", 3), ("", 4)]);
+        assert_eq!(
+            errors,
+            vec![ScanError {
+                kind: ScanErrorKind::UnterminatedComment,
+                line: 3,
+                column: 12,
+                span: Span::new(34, 36),
+            }]
+        );
+    }
+
+    #[test]
+    fn lossless() {
+        let source = "// header\nvar a = 1; // one\n\n  /* doc\n */ print  a ;\t@\n\"open";
+        let (tokens, errors) = Scanner::new(source).scan_lossless();
+        assert_eq!(
+            tokens.iter().map(|t| t.to_string()).collect::<String>(),
+            source
+        );
+        assert_eq!(errors.len(), 2);
+
+        let trivia = |trivia: &[Trivia]| trivia.iter().map(|t| t.kind).collect::<Vec<_>>();
+        assert_eq!(tokens[0].token.lexeme, "var");
+        assert_eq!(
+            trivia(&tokens[0].leading),
+            vec![TriviaKind::LineComment, TriviaKind::Newline]
+        );
+        assert_eq!(tokens[4].token.lexeme, ";");
+        assert_eq!(
+            trivia(&tokens[4].trailing),
+            vec![
+                TriviaKind::Whitespace,
+                TriviaKind::LineComment,
+                TriviaKind::Newline
+            ]
+        );
+        assert_eq!(tokens[5].token.lexeme, "print");
+        assert_eq!(
+            trivia(&tokens[5].leading),
+            vec![
+                TriviaKind::Newline,
+                TriviaKind::Whitespace,
+                TriviaKind::BlockComment,
+                TriviaKind::Whitespace
+            ]
+        );
+        let eof = tokens.last().unwrap();
+        assert_eq!(eof.token.kind, TokenType::Eof);
+        assert_eq!(trivia(&eof.leading), vec![TriviaKind::Skipped]);
+        assert_eq!(eof.leading[0].text, "\"open");
+
+        for path in ["test1.lox", "test2.lox", "test3.lox"] {
+            let source = fs::read_to_string(path).expect("Failed to read file");
+            let (tokens, _) = Scanner::new(&source).scan_lossless();
+            assert_eq!(
+                tokens.iter().map(|t| t.to_string()).collect::<String>(),
+                source
+            );
+        }
+    }
 
+    #[test]
+    fn spans() {
+        let source = "\"a\nb\" é\n  x";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let positions: Vec<_> = tokens.iter().map(|t| (t.line, t.column, t.span)).collect();
+        assert_eq!(
+            positions,
+            vec![
+                (1, 1, Span::new(0, 5)),
+                (2, 4, Span::new(6, 8)),
+                (3, 3, Span::new(11, 12)),
+                (3, 4, Span::new(12, 12)),
+            ]
+        );
+    }
+}
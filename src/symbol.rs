@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ptr;
+use std::rc::Rc;
+use std::sync::{LazyLock, Mutex};
+
+// Every string interned so far. They are leaked, so only names and literals
+// from the source get interned, never strings built at runtime.
+static INTERNER: LazyLock<Mutex<HashMap<&'static str, Symbol>>> = LazyLock::new(Default::default);
+
+// Names the runtimes look up on every call or bind, interned once up front so
+// those paths don't go through the interner's lock
+pub static INIT: LazyLock<Symbol> = LazyLock::new(|| Symbol::intern("init"));
+pub static THIS: LazyLock<Symbol> = LazyLock::new(|| Symbol::intern("this"));
+pub static SUPER: LazyLock<Symbol> = LazyLock::new(|| Symbol::intern("super"));
+
+// An interned string. Each distinct string is stored once, so symbols are
+// compared and hashed by address instead of by contents. Pointing at a
+// `String` rather than a `str` keeps them one word wide.
+#[derive(Clone, Copy)]
+pub struct Symbol(&'static String);
+
+impl Symbol {
+    pub fn intern(s: &str) -> Symbol {
+        let mut interner = INTERNER.lock().unwrap();
+        if let Some(&symbol) = interner.get(s) {
+            return symbol;
+        }
+        let symbol = Symbol(Box::leak(Box::new(s.to_owned())));
+        interner.insert(symbol.as_str(), symbol);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ptr::hash(self.0, state);
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// A string value. Literals are interned, while strings built by the program
+// are reference counted so they can be freed.
+#[derive(Clone)]
+pub enum LoxString {
+    Interned(Symbol),
+    Owned(Rc<str>),
+}
+
+impl LoxString {
+    pub fn as_str(&self) -> &str {
+        match self {
+            LoxString::Interned(symbol) => symbol.as_str(),
+            LoxString::Owned(s) => s,
+        }
+    }
+}
+
+impl PartialEq for LoxString {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LoxString::Interned(a), LoxString::Interned(b)) => a == b,
+            _ => self.as_str() == other.as_str(),
+        }
+    }
+}
+
+impl From<Symbol> for LoxString {
+    fn from(symbol: Symbol) -> Self {
+        LoxString::Interned(symbol)
+    }
+}
+
+impl From<String> for LoxString {
+    fn from(s: String) -> Self {
+        LoxString::Owned(Rc::from(s))
+    }
+}
+
+impl From<&str> for LoxString {
+    fn from(s: &str) -> Self {
+        LoxString::Owned(Rc::from(s))
+    }
+}
+
+impl fmt::Debug for LoxString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for LoxString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning() {
+        let a = Symbol::intern("name");
+        let b = Symbol::intern(&String::from("name"));
+        assert_eq!(a, b);
+        assert!(ptr::eq(a.as_str(), b.as_str()));
+        assert_ne!(a, Symbol::intern("other"));
+
+        assert_eq!(LoxString::from(a), LoxString::from(b));
+        assert_eq!(LoxString::from(a), LoxString::from("name"));
+        assert_ne!(LoxString::from(a), LoxString::from("names"));
+    }
+}
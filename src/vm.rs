@@ -0,0 +1,819 @@
+use crate::chunk::{Constant, Function, Op};
+use crate::disassembler;
+use crate::error_code::ErrorCode;
+use crate::gc::{Gc, Heap, HeapObject};
+use crate::interpreter::RuntimeError;
+use crate::object::{self, BoundMethod, Class, Closure, Instance, Upvalue, Value};
+use crate::symbol::{self, LoxString, Symbol};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+const FRAMES_MAX: usize = 1024;
+
+struct CallFrame {
+    closure: Gc<Closure>,
+    // The closure's function, kept here to save a heap lookup per op
+    function: Rc<Function>,
+    ip: usize,
+    // Stack index of the frame's slot 0
+    base: usize,
+}
+
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Symbol, Value>,
+    // Upvalues whose variables are still on the stack
+    open_upvalues: Vec<Gc<Upvalue>>,
+    heap: Heap,
+    // Print the stack and each op to stderr as it runs
+    trace: bool,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut globals = HashMap::new();
+        for native in object::natives() {
+            globals.insert(Symbol::intern(native.name), Value::Native(Rc::new(native)));
+        }
+        Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals,
+            open_upvalues: Vec::new(),
+            heap: Heap::new(),
+            trace: false,
+        }
+    }
+
+    pub fn with_trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    pub fn with_gc_stress(mut self, stress: bool) -> Self {
+        self.heap = self.heap.with_stress(stress);
+        self
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    // Runs a compiled script and returns the value it returns. Globals are
+    // kept for the next script.
+    pub fn interpret(&mut self, script: Rc<Function>) -> Result<Value, RuntimeError> {
+        let closure = self.alloc(Closure {
+            function: script,
+            upvalues: Vec::new(),
+        });
+        self.stack.push(Value::Closure(closure));
+        self.call(closure, 0)?;
+        let result = self.run();
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    fn run(&mut self) -> Result<Value, RuntimeError> {
+        loop {
+            if self.trace {
+                self.trace_op();
+            }
+            let frame = self.frames.last_mut().unwrap();
+            let op = frame.function.chunk.code[frame.ip];
+            frame.ip += 1;
+            match op {
+                Op::Constant(index) => {
+                    let value = match self.constant(index) {
+                        Constant::Num(n) => Value::Num(*n),
+                        Constant::Str(s) => Value::Str(LoxString::Interned(*s)),
+                        Constant::Function(_) => {
+                            unreachable!("functions are loaded by Op::Closure")
+                        }
+                    };
+                    self.stack.push(value);
+                }
+                Op::Nil => self.stack.push(Value::Nil),
+                Op::True => self.stack.push(Value::Bool(true)),
+                Op::False => self.stack.push(Value::Bool(false)),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::GetLocal(slot) => {
+                    let value = self.stack[self.frame().base + slot as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::SetLocal(slot) => {
+                    let slot = self.frame().base + slot as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                Op::GetGlobal(index) => {
+                    let name = self.name(index);
+                    match self.globals.get(&name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(self.undefined_variable(name)),
+                    }
+                }
+                Op::DefineGlobal(index) => {
+                    let name = self.name(index);
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                Op::SetGlobal(index) => {
+                    let name = self.name(index);
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(&name) {
+                        Some(slot) => *slot = value,
+                        None => return Err(self.undefined_variable(name)),
+                    }
+                }
+                Op::GetUpvalue(index) => {
+                    let value = match self.heap.get(self.upvalue(index)) {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                Op::SetUpvalue(index) => {
+                    let value = self.peek(0).clone();
+                    match self.heap.get_mut(self.upvalue(index)) {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                // Fields shadow methods; methods are bound to the instance on
+                // access
+                Op::GetProperty(index) => {
+                    let Value::Instance(instance) = *self.peek(0) else {
+                        return Err(
+                            self.error(ErrorCode::NotAnInstance, "Only instances have properties.")
+                        );
+                    };
+                    let name = self.name(index);
+                    let instance = self.heap.get(instance);
+                    let value = match instance.fields.get(&name) {
+                        Some(value) => value.clone(),
+                        None => {
+                            let class = instance.class;
+                            self.bind_method(class, name, self.peek(0).clone())?
+                        }
+                    };
+                    self.pop();
+                    self.stack.push(value);
+                }
+                Op::SetProperty(index) => {
+                    let Value::Instance(instance) = *self.peek(1) else {
+                        return Err(
+                            self.error(ErrorCode::NotAnInstance, "Only instances have fields.")
+                        );
+                    };
+                    let name = self.name(index);
+                    let value = self.pop();
+                    self.heap
+                        .get_mut(instance)
+                        .fields
+                        .insert(name, value.clone());
+                    self.pop();
+                    self.stack.push(value);
+                }
+                // The receiver stays on the stack while the method is bound,
+                // so a collection can't free it
+                Op::GetSuper(index) => {
+                    let name = self.name(index);
+                    let Value::Class(superclass) = *self.peek(0) else {
+                        return Err(self.invalid_bytecode("'super' is not a class"));
+                    };
+                    let method = self.bind_method(superclass, name, self.peek(1).clone())?;
+                    self.pop_pair();
+                    self.stack.push(method);
+                }
+                Op::Equal => {
+                    let (a, b) = self.pop_pair();
+                    self.stack.push(Value::Bool(a == b));
+                }
+                Op::NotEqual => {
+                    let (a, b) = self.pop_pair();
+                    self.stack.push(Value::Bool(a != b));
+                }
+                Op::Greater
+                | Op::GreaterEqual
+                | Op::Less
+                | Op::LessEqual
+                | Op::Subtract
+                | Op::Multiply
+                | Op::Divide => self.arithmetic(op)?,
+                Op::Add => match self.pop_pair() {
+                    (Value::Num(a), Value::Num(b)) => self.stack.push(Value::Num(a + b)),
+                    (Value::Str(a), Value::Str(b)) => {
+                        self.stack.push(Value::Str(format!("{a}{b}").into()))
+                    }
+                    _ => {
+                        return Err(self.error(
+                            ErrorCode::AddOperands,
+                            "Operands must be two numbers or two strings.",
+                        ))
+                    }
+                },
+                Op::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(!value.is_truthy()));
+                }
+                Op::Negate => {
+                    let Value::Num(n) = *self.peek(0) else {
+                        return Err(
+                            self.error(ErrorCode::NumberOperands, "Operand must be a number.")
+                        );
+                    };
+                    self.pop();
+                    self.stack.push(Value::Num(-n));
+                }
+                Op::Interpolate(count) => {
+                    let start = self.stack.len() - count as usize;
+                    let s: String = self.stack[start..]
+                        .iter()
+                        .map(|v| v.display(&self.heap).to_string())
+                        .collect();
+                    self.stack.truncate(start);
+                    self.stack.push(Value::Str(s.into()));
+                }
+                Op::Print => {
+                    let value = self.pop();
+                    println!("{}", value.display(&self.heap));
+                }
+                Op::Jump(target) => self.frames.last_mut().unwrap().ip = target as usize,
+                Op::JumpIfFalse(target) => {
+                    if !self.peek(0).is_truthy() {
+                        self.frames.last_mut().unwrap().ip = target as usize;
+                    }
+                }
+                Op::Call(argc) => {
+                    let callee = self.peek(argc as usize).clone();
+                    self.call_value(callee, argc as usize)?;
+                }
+                Op::Invoke(index, argc) => {
+                    let name = self.name(index);
+                    self.invoke(name, argc as usize)?;
+                }
+                Op::SuperInvoke(index, argc) => {
+                    let name = self.name(index);
+                    let Value::Class(superclass) = self.pop() else {
+                        return Err(self.invalid_bytecode("'super' is not a class"));
+                    };
+                    self.invoke_from_class(superclass, name, argc as usize)?;
+                }
+                Op::Closure(index) => {
+                    let Constant::Function(function) = self.constant(index).clone() else {
+                        unreachable!("closures are always made from functions");
+                    };
+                    // Captured upvalues are either open, or belong to the
+                    // running closure, so they survive collections made while
+                    // the rest are captured
+                    let mut upvalues = Vec::with_capacity(function.upvalues.len());
+                    for upvalue in &function.upvalues {
+                        upvalues.push(if upvalue.is_local {
+                            let slot = self.frame().base + upvalue.index as usize;
+                            self.capture_upvalue(slot)
+                        } else {
+                            self.upvalue(upvalue.index)
+                        });
+                    }
+                    let closure = self.alloc(Closure { function, upvalues });
+                    self.stack.push(Value::Closure(closure));
+                }
+                Op::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                Op::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+                    if self.frames.is_empty() {
+                        return Ok(result);
+                    }
+                    self.stack.push(result);
+                }
+                Op::Class(index) => {
+                    let class = self.alloc(Class {
+                        name: self.name(index),
+                        methods: HashMap::new(),
+                    });
+                    self.stack.push(Value::Class(class));
+                }
+                Op::Inherit => {
+                    let Value::Class(superclass) = *self.peek(1) else {
+                        return Err(self
+                            .error(ErrorCode::SuperclassNotClass, "Superclass must be a class."));
+                    };
+                    let Value::Class(subclass) = self.pop() else {
+                        return Err(self.invalid_bytecode("only classes can inherit"));
+                    };
+                    let methods = self.heap.get(superclass).methods.clone();
+                    self.heap.get_mut(subclass).methods.extend(methods);
+                }
+                Op::Method(index) => {
+                    let name = self.name(index);
+                    let Value::Closure(method) = self.pop() else {
+                        return Err(self.invalid_bytecode("method is not a function"));
+                    };
+                    let Value::Class(class) = *self.peek(0) else {
+                        return Err(self.invalid_bytecode("method is not defined on a class"));
+                    };
+                    self.heap.get_mut(class).methods.insert(name, method);
+                }
+            }
+        }
+    }
+
+    // Frees every object the program can no longer reach
+    pub fn collect_garbage(&mut self) {
+        let before = self.heap.live();
+        for value in self.stack.iter().chain(self.globals.values()) {
+            self.heap.mark_value(value);
+        }
+        // A bound method's receiver replaces the closure in slot 0
+        for frame in &self.frames {
+            self.heap.mark(frame.closure);
+        }
+        for &upvalue in &self.open_upvalues {
+            self.heap.mark(upvalue);
+        }
+        self.heap.collect();
+        if self.trace {
+            let after = self.heap.live();
+            eprintln!("-- gc: freed {} objects, {after} live", before - after);
+        }
+    }
+
+    // Anything the new object refers to must already be reachable from the
+    // roots
+    fn alloc<T: HeapObject>(&mut self, object: T) -> Gc<T> {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.alloc(object)
+    }
+
+    fn trace_op(&self) {
+        let stack: String = self
+            .stack
+            .iter()
+            .map(|v| format!("[ {} ]", v.display(&self.heap)))
+            .collect();
+        let frame = self.frame();
+        eprintln!("          {stack}");
+        eprint!(
+            "{}",
+            disassembler::instruction(&frame.function.chunk, frame.ip)
+        );
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn constant(&self, index: u32) -> &Constant {
+        &self.frame().function.chunk.constants[index as usize]
+    }
+
+    fn upvalue(&self, index: u32) -> Gc<Upvalue> {
+        self.heap.get(self.frame().closure).upvalues[index as usize]
+    }
+
+    fn name(&self, index: u32) -> Symbol {
+        match self.constant(index) {
+            Constant::Str(name) => *name,
+            constant => unreachable!("expected a name, found {constant:?}"),
+        }
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    // Pops two operands, returning them in the order they were pushed
+    fn pop_pair(&mut self) -> (Value, Value) {
+        let b = self.pop();
+        let a = self.pop();
+        (a, b)
+    }
+
+    // Reports an error at the op currently being executed
+    fn error(&self, code: ErrorCode, message: &str) -> RuntimeError {
+        let frame = self.frame();
+        let chunk = &frame.function.chunk;
+        RuntimeError {
+            code,
+            message: message.to_owned(),
+            line: chunk.lines[frame.ip - 1],
+            span: chunk.spans[frame.ip - 1],
+        }
+    }
+
+    // The compiler never emits code that gets here, but a hand-edited .loxc
+    // file can
+    fn invalid_bytecode(&self, what: &str) -> RuntimeError {
+        self.error(
+            ErrorCode::InvalidBytecode,
+            &format!("Invalid bytecode: {what}."),
+        )
+    }
+
+    fn undefined_variable(&self, name: Symbol) -> RuntimeError {
+        self.error(
+            ErrorCode::UndefinedVariable,
+            &format!("Undefined variable '{name}'."),
+        )
+    }
+
+    fn arithmetic(&mut self, op: Op) -> Result<(), RuntimeError> {
+        let (&Value::Num(a), &Value::Num(b)) = (self.peek(1), self.peek(0)) else {
+            return Err(self.error(ErrorCode::NumberOperands, "Operands must be numbers."));
+        };
+        let value = match op {
+            Op::Greater => Value::Bool(a > b),
+            Op::GreaterEqual => Value::Bool(a >= b),
+            Op::Less => Value::Bool(a < b),
+            Op::LessEqual => Value::Bool(a <= b),
+            Op::Subtract => Value::Num(a - b),
+            Op::Multiply => Value::Num(a * b),
+            Op::Divide => Value::Num(a / b),
+            _ => unreachable!("invalid arithmetic op {op:?}"),
+        };
+        self.pop_pair();
+        self.stack.push(value);
+        Ok(())
+    }
+
+    // The callee sits below its arguments on the stack, in the slot that
+    // becomes slot 0 of the new frame
+    fn call_value(&mut self, callee: Value, argc: usize) -> Result<(), RuntimeError> {
+        let slot = self.stack.len() - argc - 1;
+        match callee {
+            Value::Closure(closure) => self.call(closure, argc),
+            Value::Native(native) => {
+                self.check_arity(native.arity, argc)?;
+                let result = (native.function)(&self.stack[slot + 1..]);
+                self.stack.truncate(slot);
+                self.stack.push(result);
+                Ok(())
+            }
+            Value::Class(class) => {
+                let instance = self.alloc(Instance {
+                    class,
+                    fields: HashMap::new(),
+                });
+                self.stack[slot] = Value::Instance(instance);
+                let init = self.heap.get(class).methods.get(&symbol::INIT).copied();
+                match init {
+                    Some(init) => self.call(init, argc),
+                    None => self.check_arity(0, argc),
+                }
+            }
+            Value::BoundMethod(bound) => {
+                let bound = self.heap.get(bound);
+                let method = bound.method;
+                self.stack[slot] = bound.receiver.clone();
+                self.call(method, argc)
+            }
+            _ => Err(self.error(
+                ErrorCode::NotCallable,
+                "Can only call functions and classes.",
+            )),
+        }
+    }
+
+    fn call(&mut self, closure: Gc<Closure>, argc: usize) -> Result<(), RuntimeError> {
+        let function = self.heap.get(closure).function.clone();
+        self.check_arity(function.arity, argc)?;
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.error(ErrorCode::StackOverflow, "Stack overflow."));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            function,
+            ip: 0,
+            base: self.stack.len() - argc - 1,
+        });
+        Ok(())
+    }
+
+    fn check_arity(&self, arity: usize, argc: usize) -> Result<(), RuntimeError> {
+        if arity == argc {
+            return Ok(());
+        }
+        Err(self.error(
+            ErrorCode::ArityMismatch,
+            &format!("Expected {arity} arguments but got {argc}."),
+        ))
+    }
+
+    // Calls the method `name` on the receiver below the arguments
+    fn invoke(&mut self, name: Symbol, argc: usize) -> Result<(), RuntimeError> {
+        let Value::Instance(instance) = *self.peek(argc) else {
+            return Err(self.error(ErrorCode::NotAnInstance, "Only instances have properties."));
+        };
+        let instance = self.heap.get(instance);
+        if let Some(field) = instance.fields.get(&name).cloned() {
+            let slot = self.stack.len() - argc - 1;
+            self.stack[slot] = field.clone();
+            return self.call_value(field, argc);
+        }
+        self.invoke_from_class(instance.class, name, argc)
+    }
+
+    fn invoke_from_class(
+        &mut self,
+        class: Gc<Class>,
+        name: Symbol,
+        argc: usize,
+    ) -> Result<(), RuntimeError> {
+        let method = self.heap.get(class).methods.get(&name).copied();
+        match method {
+            Some(method) => self.call(method, argc),
+            None => Err(self.undefined_property(name)),
+        }
+    }
+
+    // The receiver must be on the stack, since binding allocates
+    fn bind_method(
+        &mut self,
+        class: Gc<Class>,
+        name: Symbol,
+        receiver: Value,
+    ) -> Result<Value, RuntimeError> {
+        let method = self.heap.get(class).methods.get(&name).copied();
+        match method {
+            Some(method) => {
+                let bound = self.alloc(BoundMethod { receiver, method });
+                Ok(Value::BoundMethod(bound))
+            }
+            None => Err(self.undefined_property(name)),
+        }
+    }
+
+    fn undefined_property(&self, name: Symbol) -> RuntimeError {
+        self.error(
+            ErrorCode::UndefinedProperty,
+            &format!("Undefined property '{name}'."),
+        )
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Gc<Upvalue> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|&&upvalue| matches!(self.heap.get(upvalue), Upvalue::Open(s) if *s == slot));
+        if let Some(&upvalue) = existing {
+            return upvalue;
+        }
+        let upvalue = self.alloc(Upvalue::Open(slot));
+        self.open_upvalues.push(upvalue);
+        upvalue
+    }
+
+    // Moves variables at or above stack index `from` into their upvalues
+    fn close_upvalues(&mut self, from: usize) {
+        let (heap, stack) = (&mut self.heap, &self.stack);
+        self.open_upvalues.retain(|&upvalue| {
+            let upvalue = heap.get_mut(upvalue);
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= from => {
+                    *upvalue = Upvalue::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler;
+    use crate::diagnostics::SourceFile;
+    use crate::parser::Parser;
+    use crate::resolver;
+    use crate::scanner::{Scanner, Span};
+
+    fn run(vm: &mut Vm, source: &str) -> Result<Value, RuntimeError> {
+        let file = SourceFile::new("test.lox", source);
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        resolver::resolve(&program).unwrap();
+        vm.interpret(compiler::compile(&file, &program))
+    }
+
+    fn eval(source: &str) -> Result<Value, RuntimeError> {
+        let file = SourceFile::new("test.lox", source);
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let expr = Parser::new(tokens).parse().unwrap();
+        Vm::new().interpret(compiler::compile_expression(&file, &expr))
+    }
+
+    fn global(vm: &Vm, name: &str) -> Option<Value> {
+        vm.globals.get(&Symbol::intern(name)).cloned()
+    }
+
+    fn str(s: &str) -> Value {
+        Value::Str(s.into())
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(eval("1 + 2 * 3 - 4 / 2"), Ok(Value::Num(5.0)));
+        assert_eq!(eval("-(1 + 2) >= -3 == !nil"), Ok(Value::Bool(true)));
+        assert_eq!(eval("\"a\" + \"b\" != \"ab\""), Ok(Value::Bool(false)));
+        assert_eq!(eval("nil or 2 and 3"), Ok(Value::Num(3.0)));
+        assert_eq!(eval("false and undefined"), Ok(Value::Bool(false)));
+        assert_eq!(eval("\"${1 + 1} and ${nil}\""), Ok(str("2 and nil")));
+    }
+
+    #[test]
+    fn globals_and_control_flow() {
+        let mut vm = Vm::new();
+        let source = "
+            var sum = 0;
+            for (var i = 0; i < 5; i = i + 1) {
+                if (i == 2) sum = sum + 100; else sum = sum + i;
+            }
+            var n;
+            { var m = 3; while (m > 0) m = m - 1; n = m; }
+        ";
+        assert_eq!(run(&mut vm, source), Ok(Value::Nil));
+        assert_eq!(global(&vm, "sum"), Some(Value::Num(108.0)));
+        assert_eq!(global(&vm, "n"), Some(Value::Num(0.0)));
+        // Globals persist between scripts
+        assert_eq!(run(&mut vm, "sum = sum + 1;"), Ok(Value::Nil));
+        assert_eq!(global(&vm, "sum"), Some(Value::Num(109.0)));
+    }
+
+    #[test]
+    fn functions_and_closures() {
+        let source = "
+            fun fib(n) {
+                if (n < 2) return n;
+                return fib(n - 1) + fib(n - 2);
+            }
+            fun makeCounter() {
+                var i = 0;
+                fun count() {
+                    i = i + 1;
+                    return i;
+                }
+                return count;
+            }
+            var counter = makeCounter();
+            counter();
+            var a = counter();
+            var b = fib(10);
+            var get;
+            var set;
+            {
+                var shared = 1;
+                fun g() { return shared; }
+                fun s(value) { shared = value; }
+                get = g;
+                set = s;
+            }
+            set(5);
+            var c = get();
+        ";
+        // Collecting before every allocation must not free anything in use
+        for mut vm in [Vm::new(), Vm::new().with_gc_stress(true)] {
+            assert_eq!(run(&mut vm, source), Ok(Value::Nil));
+            assert_eq!(global(&vm, "a"), Some(Value::Num(2.0)));
+            assert_eq!(global(&vm, "b"), Some(Value::Num(55.0)));
+            assert_eq!(global(&vm, "c"), Some(Value::Num(5.0)));
+            assert_eq!(
+                global(&vm, "fib").map(|f| f.display(&vm.heap).to_string()),
+                Some("<fn fib>".to_owned())
+            );
+        }
+        assert!(matches!(eval("clock()"), Ok(Value::Num(_))));
+    }
+
+    #[test]
+    fn classes() {
+        let source = "
+            class Point {
+                init(x, y) {
+                    this.x = x;
+                    this.y = y;
+                }
+                sum() { return this.x + this.y; }
+            }
+            class Point3 < Point {
+                init(x, y, z) {
+                    super.init(x, y);
+                    this.z = z;
+                }
+                sum() { return super.sum() + this.z; }
+            }
+            var p = Point3(1, 2, 3);
+            var sum = p.sum;
+            var total = sum();
+            var again = p.init(4, 5, 6) == p;
+            var fields = p.x + p.z;
+            fun double() { return 2 * p.y; }
+            p.sum = double;
+            var shadowed = p.sum();
+        ";
+        for mut vm in [Vm::new(), Vm::new().with_gc_stress(true)] {
+            assert_eq!(run(&mut vm, source), Ok(Value::Nil));
+            assert_eq!(global(&vm, "total"), Some(Value::Num(6.0)));
+            assert_eq!(global(&vm, "again"), Some(Value::Bool(true)));
+            assert_eq!(global(&vm, "fields"), Some(Value::Num(10.0)));
+            assert_eq!(global(&vm, "shadowed"), Some(Value::Num(10.0)));
+            assert_eq!(
+                global(&vm, "p").map(|p| p.display(&vm.heap).to_string()),
+                Some("Point3 instance".to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn collects_cycles() {
+        let mut vm = Vm::new();
+        let source = "
+            class Node {
+                init() { this.next = this; }
+            }
+            fun pair() {
+                var f;
+                fun g() { return f; }
+                fun h() { return g; }
+                f = h;
+                return g;
+            }
+            for (var i = 0; i < 2000; i = i + 1) {
+                Node();
+                pair();
+            }
+            var kept = Node();
+        ";
+        assert_eq!(run(&mut vm, source), Ok(Value::Nil));
+        // Collections ran along the way
+        assert!(vm.heap.live() < 2000);
+        vm.collect_garbage();
+        // Node, its initializer, pair and kept
+        assert_eq!(vm.heap.live(), 4);
+        assert_eq!(
+            global(&vm, "kept").map(|k| k.display(&vm.heap).to_string()),
+            Some("Node instance".to_owned())
+        );
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let mut vm = Vm::new();
+        assert_eq!(
+            run(&mut vm, "var a = 1;\nprint a + nil;"),
+            Err(RuntimeError {
+                code: ErrorCode::AddOperands,
+                message: "Operands must be two numbers or two strings.".to_owned(),
+                line: 2,
+                span: Span::new(17, 24),
+            })
+        );
+        // The VM is usable again after an error
+        assert_eq!(
+            run(&mut vm, "fun f(x) {}\nf();"),
+            Err(RuntimeError {
+                code: ErrorCode::ArityMismatch,
+                message: "Expected 1 arguments but got 0.".to_owned(),
+                line: 2,
+                span: Span::new(12, 15),
+            })
+        );
+        let message = |source| run(&mut Vm::new(), source).unwrap_err().message;
+        assert_eq!(
+            message("print undefined;"),
+            "Undefined variable 'undefined'."
+        );
+        assert_eq!(message("nil();"), "Can only call functions and classes.");
+        assert_eq!(
+            message("var a = 1; a.b = 2;"),
+            "Only instances have fields."
+        );
+        assert_eq!(message("class A {} A().b();"), "Undefined property 'b'.");
+        assert_eq!(
+            message("var NotAClass = 1; class Oops < NotAClass {}"),
+            "Superclass must be a class."
+        );
+        assert_eq!(
+            run(&mut vm, "fun f() { return f(); }\nf();").map_err(|e| e.code),
+            Err(ErrorCode::StackOverflow)
+        );
+    }
+}